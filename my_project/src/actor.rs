@@ -0,0 +1,361 @@
+#![allow(dead_code)]
+
+// 轻量级 Actor 框架
+// 很多模拟代码都在手写“一个线程 + 一个通道 + 一个循环”。Actor 把这个模式固定下来：
+// 每个 actor 有自己的线程和邮箱（mailbox，即 mpsc 通道），一次只处理一条消息，所以状态不需要加锁。
+// 外部通过 ActorRef 发送消息：send 不等待结果，call 携带一个回复通道并带超时地等待回复。
+// 处理消息时 panic 的 actor 会被重新创建（状态重置，邮箱保留），一定时间内重启太多次则停止。
+// 重新创建时 factory 或 started panic 也算一次重启。
+// 所有 ActorRef 都 drop 后 actor 自行退出；ActorSystem 只保存邮箱的弱引用，不会让没人用的 actor 一直占着线程。
+// ActorSystem 按启动顺序的逆序关闭各个 actor，后启动的（通常依赖先启动的）先停。
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::i18n::tr;
+use crate::pool::{panic_message, run_catching};
+
+pub trait Actor: Send + 'static {
+    type Msg: Send + 'static;
+
+    fn handle(&mut self, msg: Self::Msg);
+
+    // 每次（重新）创建之后、处理第一条消息之前调用
+    fn started(&mut self) {}
+
+    // actor 线程退出前调用
+    fn stopped(&mut self) {}
+}
+
+// call 的回复端，actor 在处理消息时调用 reply
+pub struct ReplyTo<R>(Sender<R>);
+
+impl<R> ReplyTo<R> {
+    pub fn reply(self, value: R) {
+        let _ = self.0.send(value); // 调用者可能已经超时放弃
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallError {
+    Timeout,
+    Stopped, // actor 已停止，或者处理消息时 panic 而没有回复
+}
+
+impl fmt::Display for CallError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CallError::Timeout => write!(f, "actor did not reply in time"),
+            CallError::Stopped => write!(f, "actor stopped without replying"),
+        }
+    }
+}
+
+impl Error for CallError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActorStopped;
+
+impl fmt::Display for ActorStopped {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "actor has stopped")
+    }
+}
+
+impl Error for ActorStopped {}
+
+enum Envelope<M> {
+    Msg(M),
+    Stop,
+}
+
+// 所有 ActorRef 共享同一个 Sender，ActorSystem 只持有它的 Weak
+pub struct ActorRef<M> {
+    name: String,
+    tx: Arc<Sender<Envelope<M>>>,
+}
+
+impl<M> Clone for ActorRef<M> {
+    fn clone(&self) -> Self {
+        ActorRef { name: self.name.clone(), tx: Arc::clone(&self.tx) }
+    }
+}
+
+impl<M: Send + 'static> ActorRef<M> {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // 发送后立即返回（fire-and-forget）
+    pub fn send(&self, msg: M) -> Result<(), ActorStopped> {
+        self.tx.send(Envelope::Msg(msg)).map_err(|_| ActorStopped)
+    }
+
+    // 请求 / 回复：make 用回复端构造消息，例如 `counter.call(CounterMsg::Get, timeout)`
+    pub fn call<R>(&self, make: impl FnOnce(ReplyTo<R>) -> M, timeout: Duration) -> Result<R, CallError> {
+        let (tx, rx) = mpsc::channel();
+        self.send(make(ReplyTo(tx))).map_err(|_| CallError::Stopped)?;
+        rx.recv_timeout(timeout).map_err(|e| match e {
+            mpsc::RecvTimeoutError::Timeout => CallError::Timeout,
+            mpsc::RecvTimeoutError::Disconnected => CallError::Stopped,
+        })
+    }
+}
+
+// 重启策略：window 时间内最多重启 max_restarts 次，超过后 actor 停止
+#[derive(Debug, Clone, Copy)]
+pub struct Supervision {
+    pub max_restarts: usize,
+    pub window: Duration,
+}
+
+impl Default for Supervision {
+    fn default() -> Self {
+        Supervision { max_restarts: 3, window: Duration::from_secs(10) }
+    }
+}
+
+struct Running {
+    name: String,
+    stop: Box<dyn Fn() + Send>,
+    handle: JoinHandle<()>,
+}
+
+pub struct ActorSystem {
+    actors: Mutex<Vec<Running>>,
+}
+
+impl Default for ActorSystem {
+    fn default() -> Self {
+        ActorSystem::new()
+    }
+}
+
+impl ActorSystem {
+    pub fn new() -> ActorSystem {
+        ActorSystem { actors: Mutex::new(vec![]) }
+    }
+
+    // factory 用于创建 actor，panic 后也用它重新创建
+    pub fn spawn<A, F>(&self, name: &str, factory: F) -> ActorRef<A::Msg>
+    where
+        A: Actor,
+        F: Fn() -> A + Send + 'static,
+    {
+        self.spawn_supervised(name, Supervision::default(), factory)
+    }
+
+    pub fn spawn_supervised<A, F>(&self, name: &str, supervision: Supervision, factory: F) -> ActorRef<A::Msg>
+    where
+        A: Actor,
+        F: Fn() -> A + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+        let thread_name = name.to_string();
+        let handle = thread::Builder::new()
+            .name(format!("actor-{}", name))
+            .spawn(move || run_actor(&thread_name, rx, supervision, factory))
+            .expect("failed to spawn actor thread");
+        let tx = Arc::new(tx);
+        let stop_tx: Weak<Sender<Envelope<A::Msg>>> = Arc::downgrade(&tx);
+        self.actors.lock().unwrap().push(Running {
+            name: name.to_string(),
+            // 升级失败说明 ActorRef 都已 drop，邮箱已经断开，actor 会自己退出
+            stop: Box::new(move || {
+                if let Some(tx) = stop_tx.upgrade() {
+                    let _ = tx.send(Envelope::Stop);
+                }
+            }),
+            handle,
+        });
+        ActorRef { name: name.to_string(), tx }
+    }
+
+    // 按启动顺序的逆序逐个停止：先发送 Stop，actor 处理完之前排队的消息后退出，再 join 它的线程
+    pub fn shutdown(&self) {
+        let actors: Vec<Running> = self.actors.lock().unwrap().drain(..).collect();
+        for actor in actors.into_iter().rev() {
+            (actor.stop)();
+            if actor.handle.join().is_err() {
+                eprintln!("{}", tr!("actor.stop_panicked", name = actor.name));
+            }
+        }
+    }
+}
+
+impl Drop for ActorSystem {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn run_actor<A, F>(name: &str, rx: Receiver<Envelope<A::Msg>>, supervision: Supervision, factory: F)
+where
+    A: Actor,
+    F: Fn() -> A,
+{
+    let mut restarts: VecDeque<Instant> = VecDeque::new();
+    // None 表示还没有创建，或者刚刚 panic 过，需要重新创建
+    let mut actor: Option<A> = None;
+    loop {
+        let result = match actor.as_mut() {
+            None => run_catching(|| {
+                let mut created = factory();
+                created.started();
+                actor = Some(created);
+            }),
+            // 所有 ActorRef 都 drop 后 recv 返回 Err，收到 Stop 也退出
+            Some(current) => match rx.recv() {
+                Ok(Envelope::Msg(msg)) => run_catching(|| current.handle(msg)),
+                Ok(Envelope::Stop) | Err(_) => break,
+            },
+        };
+        let payload = match result {
+            Ok(()) => continue,
+            Err(payload) => payload,
+        };
+        // panic 过的实例状态可能不完整，直接丢弃，不调用 stopped
+        actor = None;
+        let now = Instant::now();
+        while restarts.front().is_some_and(|t| now.duration_since(*t) > supervision.window) {
+            restarts.pop_front();
+        }
+        if restarts.len() >= supervision.max_restarts {
+            eprintln!("{}", tr!("actor.gave_up", name = name, message = panic_message(&*payload)));
+            return;
+        }
+        restarts.push_back(now);
+    }
+    if let Some(mut actor) = actor {
+        actor.stopped();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    enum CounterMsg {
+        Add(u64),
+        Get(ReplyTo<u64>),
+        Crash,
+        Sleep(Duration),
+    }
+
+    struct Counter {
+        total: u64,
+        log: Arc<Mutex<Vec<String>>>,
+        name: &'static str,
+    }
+
+    impl Actor for Counter {
+        type Msg = CounterMsg;
+
+        fn handle(&mut self, msg: CounterMsg) {
+            match msg {
+                CounterMsg::Add(n) => self.total += n,
+                CounterMsg::Get(reply) => reply.reply(self.total),
+                CounterMsg::Crash => panic!("counter crashed"),
+                CounterMsg::Sleep(d) => thread::sleep(d),
+            }
+        }
+
+        fn stopped(&mut self) {
+            self.log.lock().unwrap().push(self.name.to_string());
+        }
+    }
+
+    fn counter(name: &'static str, log: &Arc<Mutex<Vec<String>>>) -> impl Fn() -> Counter + Send + 'static {
+        let log = Arc::clone(log);
+        move || Counter { total: 0, log: Arc::clone(&log), name }
+    }
+
+    #[test]
+    fn send_and_call() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let system = ActorSystem::new();
+        let c = system.spawn("counter", counter("counter", &log));
+        for i in 1..=10 {
+            c.send(CounterMsg::Add(i)).unwrap();
+        }
+        assert_eq!(c.call(CounterMsg::Get, Duration::from_secs(1)), Ok(55));
+
+        c.send(CounterMsg::Sleep(Duration::from_millis(100))).unwrap();
+        assert_eq!(c.call(CounterMsg::Get, Duration::from_millis(10)), Err(CallError::Timeout));
+    }
+
+    #[test]
+    fn panicking_actor_is_restarted_with_fresh_state() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let system = ActorSystem::new();
+        let supervision = Supervision { max_restarts: 1, window: Duration::from_secs(60) };
+        let c = system.spawn_supervised("flaky", supervision, counter("flaky", &log));
+        c.send(CounterMsg::Add(5)).unwrap();
+        c.send(CounterMsg::Crash).unwrap();
+        c.send(CounterMsg::Add(1)).unwrap();
+        assert_eq!(c.call(CounterMsg::Get, Duration::from_secs(1)), Ok(1));
+
+        c.send(CounterMsg::Crash).unwrap(); // 超过重启上限，actor 停止
+        assert_eq!(c.call(CounterMsg::Get, Duration::from_secs(1)), Err(CallError::Stopped));
+    }
+
+    #[test]
+    fn shutdown_stops_actors_in_reverse_order() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let system = ActorSystem::new();
+        let db = system.spawn("db", counter("db", &log));
+        let cache = system.spawn("cache", counter("cache", &log));
+        let api = system.spawn("api", counter("api", &log));
+        api.send(CounterMsg::Add(1)).unwrap();
+        system.shutdown();
+        assert_eq!(*log.lock().unwrap(), vec!["api", "cache", "db"]);
+        assert_eq!(db.send(CounterMsg::Add(1)), Err(ActorStopped));
+        drop(cache);
+    }
+
+    #[test]
+    fn actor_exits_when_all_refs_are_dropped() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let system = ActorSystem::new();
+        let c = system.spawn("orphan", counter("orphan", &log));
+        let c2 = c.clone();
+        drop(c);
+        c2.send(CounterMsg::Add(1)).unwrap();
+        drop(c2);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while log.lock().unwrap().is_empty() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(5));
+        }
+        // 不需要 shutdown，actor 已经调用了 stopped
+        assert_eq!(*log.lock().unwrap(), vec!["orphan"]);
+    }
+
+    #[test]
+    fn panics_while_restarting_count_against_the_window() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let created = Arc::new(AtomicUsize::new(0));
+        let (make, created2) = (counter("fragile", &log), Arc::clone(&created));
+        let factory = move || {
+            // 第 2、3 次创建失败
+            if (2..=3).contains(&created2.fetch_add(1, Ordering::SeqCst)) {
+                panic!("cannot connect");
+            }
+            make()
+        };
+        let system = ActorSystem::new();
+        let supervision = Supervision { max_restarts: 3, window: Duration::from_secs(60) };
+        let c = system.spawn_supervised("fragile", supervision, factory);
+        c.send(CounterMsg::Add(5)).unwrap();
+        c.send(CounterMsg::Crash).unwrap(); // 第 1 次重启，创建成功
+        c.send(CounterMsg::Crash).unwrap(); // 第 2、3 次重启失败，第 4 次超过上限
+        assert_eq!(c.call(CounterMsg::Get, Duration::from_secs(1)), Err(CallError::Stopped));
+        assert_eq!(created.load(Ordering::SeqCst), 4);
+        // panic 过的实例不会调用 stopped
+        assert!(log.lock().unwrap().is_empty());
+    }
+}
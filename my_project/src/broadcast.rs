@@ -0,0 +1,359 @@
+#![allow(dead_code)]
+
+// 广播通道与发布 / 订阅（publish-subscribe）
+// mpsc 的每条消息只会被一个消费者取走；广播通道里每个订阅者（subscriber）都能收到每一条消息。
+// 消息保存在固定容量的环形缓冲区（ring buffer）中，发送永远不会阻塞：
+// 缓冲区满了就覆盖最旧的消息，来不及读的慢订阅者会收到 Lagged(n)，表示错过了 n 条，然后从最旧的可用消息继续。
+// 订阅者可以随时加入，只会收到加入之后发送的消息。
+// PubSub 在此基础上按主题（topic）分发：每个主题一个广播通道。
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct SendError<T>(pub T); // 没有任何订阅者
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecvError {
+    Lagged(u64), // 错过了多少条消息
+    Closed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryRecvError {
+    Empty,
+    Lagged(u64),
+    Closed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecvTimeoutError {
+    Timeout,
+    Lagged(u64),
+    Closed,
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "broadcast channel has no subscribers")
+    }
+}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecvError::Lagged(n) => write!(f, "subscriber lagged behind and missed {} messages", n),
+            RecvError::Closed => write!(f, "broadcast channel is closed"),
+        }
+    }
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => write!(f, "no new messages"),
+            TryRecvError::Lagged(n) => write!(f, "subscriber lagged behind and missed {} messages", n),
+            TryRecvError::Closed => write!(f, "broadcast channel is closed"),
+        }
+    }
+}
+
+impl fmt::Display for RecvTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecvTimeoutError::Timeout => write!(f, "timed out waiting for a message"),
+            RecvTimeoutError::Lagged(n) => write!(f, "subscriber lagged behind and missed {} messages", n),
+            RecvTimeoutError::Closed => write!(f, "broadcast channel is closed"),
+        }
+    }
+}
+
+impl<T: fmt::Debug> Error for SendError<T> {}
+impl Error for RecvError {}
+impl Error for TryRecvError {}
+impl Error for RecvTimeoutError {}
+
+struct State<T> {
+    buf: Vec<Option<T>>, // 序号为 seq 的消息存放在 buf[seq % cap]
+    head: u64,           // 下一条消息的序号，也就是已经发送的消息总数
+    senders: usize,
+    receivers: usize,
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    cap: usize,
+    cond: Condvar,
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap()
+    }
+}
+
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+    next: u64, // 该订阅者下一条要读的序号
+}
+
+// 创建容量为 cap 的广播通道，返回发送端和第一个订阅者
+pub fn channel<T: Clone>(cap: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(cap > 0, "broadcast capacity must be positive");
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            buf: (0..cap).map(|_| None).collect(),
+            head: 0,
+            senders: 1,
+            receivers: 1,
+        }),
+        cap,
+        cond: Condvar::new(),
+    });
+    (Sender { shared: Arc::clone(&shared) }, Receiver { shared, next: 0 })
+}
+
+impl<T: Clone> Sender<T> {
+    // 发送给当前所有订阅者，返回订阅者个数；没有订阅者时返回 Err，消息交还给调用者
+    pub fn send(&self, value: T) -> Result<usize, SendError<T>> {
+        let mut state = self.shared.lock();
+        if state.receivers == 0 {
+            return Err(SendError(value));
+        }
+        let idx = (state.head % self.shared.cap as u64) as usize;
+        state.buf[idx] = Some(value);
+        state.head += 1;
+        self.shared.cond.notify_all();
+        Ok(state.receivers)
+    }
+
+    // 新订阅者从下一条消息开始接收
+    pub fn subscribe(&self) -> Receiver<T> {
+        let mut state = self.shared.lock();
+        state.receivers += 1;
+        Receiver { shared: Arc::clone(&self.shared), next: state.head }
+    }
+
+    pub fn receiver_count(&self) -> usize {
+        self.shared.lock().receivers
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.lock().senders += 1;
+        Sender { shared: Arc::clone(&self.shared) }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.senders -= 1;
+        if state.senders == 0 {
+            self.shared.cond.notify_all();
+        }
+    }
+}
+
+enum Polled<T> {
+    Ready(T),
+    Lagged(u64),
+    Empty,
+    Closed,
+}
+
+impl<T: Clone> Receiver<T> {
+    pub fn recv(&mut self) -> Result<T, RecvError> {
+        self.recv_deadline(None).map_err(|e| match e {
+            RecvTimeoutError::Lagged(n) => RecvError::Lagged(n),
+            RecvTimeoutError::Closed | RecvTimeoutError::Timeout => RecvError::Closed,
+        })
+    }
+
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let shared = Arc::clone(&self.shared);
+        let state = shared.lock();
+        match self.poll(&state) {
+            Polled::Ready(v) => Ok(v),
+            Polled::Lagged(n) => Err(TryRecvError::Lagged(n)),
+            Polled::Empty => Err(TryRecvError::Empty),
+            Polled::Closed => Err(TryRecvError::Closed),
+        }
+    }
+
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        self.recv_deadline(Some(Instant::now() + timeout))
+    }
+
+    fn recv_deadline(&mut self, deadline: Option<Instant>) -> Result<T, RecvTimeoutError> {
+        let shared = Arc::clone(&self.shared);
+        let mut state = shared.lock();
+        loop {
+            match self.poll(&state) {
+                Polled::Ready(v) => return Ok(v),
+                Polled::Lagged(n) => return Err(RecvTimeoutError::Lagged(n)),
+                Polled::Closed => return Err(RecvTimeoutError::Closed),
+                Polled::Empty => {}
+            }
+            state = match deadline {
+                None => shared.cond.wait(state).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(RecvTimeoutError::Timeout);
+                    }
+                    shared.cond.wait_timeout(state, deadline - now).unwrap().0
+                }
+            };
+        }
+    }
+
+    fn poll(&mut self, state: &State<T>) -> Polled<T> {
+        let oldest = state.head.saturating_sub(self.shared.cap as u64);
+        if self.next < oldest {
+            // 要读的消息已经被覆盖，跳到最旧的可用消息
+            let missed = oldest - self.next;
+            self.next = oldest;
+            return Polled::Lagged(missed);
+        }
+        if self.next < state.head {
+            let idx = (self.next % self.shared.cap as u64) as usize;
+            self.next += 1;
+            return Polled::Ready(state.buf[idx].clone().expect("slot below head is filled"));
+        }
+        if state.senders == 0 {
+            Polled::Closed
+        } else {
+            Polled::Empty
+        }
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    // 副本从同一位置开始，之后各自独立前进
+    fn clone(&self) -> Self {
+        self.shared.lock().receivers += 1;
+        Receiver { shared: Arc::clone(&self.shared), next: self.next }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.shared.lock().receivers -= 1;
+    }
+}
+
+// 按主题分发的发布 / 订阅。主题在第一次订阅时创建，每个主题一个容量为 cap 的广播通道。
+pub struct PubSub<T> {
+    topics: Mutex<HashMap<String, Sender<T>>>,
+    cap: usize,
+}
+
+impl<T: Clone> PubSub<T> {
+    pub fn new(cap: usize) -> PubSub<T> {
+        PubSub { topics: Mutex::new(HashMap::new()), cap }
+    }
+
+    pub fn subscribe(&self, topic: &str) -> Receiver<T> {
+        let mut topics = self.topics.lock().unwrap();
+        match topics.get(topic) {
+            Some(tx) => tx.subscribe(),
+            None => {
+                let (tx, rx) = channel(self.cap);
+                topics.insert(topic.to_string(), tx);
+                rx
+            }
+        }
+    }
+
+    // 发布到主题，返回收到消息的订阅者个数。订阅者已全部离开的主题会被移除。
+    pub fn publish(&self, topic: &str, value: T) -> usize {
+        let mut topics = self.topics.lock().unwrap();
+        let delivered = match topics.get(topic) {
+            Some(tx) => tx.send(value).unwrap_or(0),
+            None => return 0,
+        };
+        if delivered == 0 {
+            topics.remove(topic);
+        }
+        delivered
+    }
+
+    pub fn topics(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.topics.lock().unwrap().keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn every_subscriber_sees_every_message() {
+        let (tx, rx) = channel::<u32>(16);
+        let consumers: Vec<_> = (0..3)
+            .map(|i| {
+                let mut rx = if i == 0 { rx.clone() } else { tx.subscribe() };
+                thread::spawn(move || {
+                    let mut got = vec![];
+                    while let Ok(v) = rx.recv() {
+                        got.push(v);
+                    }
+                    got
+                })
+            })
+            .collect();
+        drop(rx);
+        for i in 0..10 {
+            assert_eq!(tx.send(i), Ok(3));
+        }
+        drop(tx);
+        for c in consumers {
+            assert_eq!(c.join().unwrap(), (0..10).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn slow_subscriber_lags() {
+        let (tx, mut rx) = channel(4);
+        for i in 0..10 {
+            tx.send(i).unwrap();
+        }
+        assert_eq!(rx.recv(), Err(RecvError::Lagged(6)));
+        assert_eq!(rx.recv(), Ok(6));
+        let mut late = tx.subscribe(); // 中途加入
+        tx.send(10).unwrap();
+        assert_eq!(late.try_recv(), Ok(10));
+        assert_eq!(late.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(late.recv_timeout(Duration::from_millis(5)), Err(RecvTimeoutError::Timeout));
+        drop(tx);
+        assert_eq!((rx.recv(), rx.recv(), rx.recv(), rx.recv()), (Ok(7), Ok(8), Ok(9), Ok(10)));
+        assert_eq!(rx.recv(), Err(RecvError::Closed));
+    }
+
+    #[test]
+    fn topics_are_isolated() {
+        let bus = PubSub::new(8);
+        let mut config = bus.subscribe("config");
+        let mut logs_a = bus.subscribe("logs");
+        let mut logs_b = bus.subscribe("logs");
+        assert_eq!(bus.publish("logs", "started".to_string()), 2);
+        assert_eq!(bus.publish("config", "reload".to_string()), 1);
+        assert_eq!(bus.publish("metrics", "ignored".to_string()), 0);
+        assert_eq!(logs_a.recv(), Ok("started".to_string()));
+        assert_eq!(logs_b.recv(), Ok("started".to_string()));
+        assert_eq!(config.try_recv(), Ok("reload".to_string()));
+        assert_eq!(config.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(bus.topics(), vec!["config", "logs"]);
+    }
+}
@@ -0,0 +1,394 @@
+#![allow(dead_code)]
+
+// 协作式取消（cooperative cancellation）
+// 线程不能被强行终止，只能由 worker 自己定期检查“是否该停下来”。
+// CancellationToken 可以 clone（所有副本共享同一个状态），也可以派生子令牌：
+// 取消父令牌时所有子令牌一起被取消，反过来则不会。令牌还可以带一个截止时间（deadline），过期即视为已取消。
+// sleep 和 recv 在等待期间也会响应取消，不必等到睡眠结束或消息到来。
+use std::error::Error;
+use std::fmt;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Condvar, Mutex, Once, Weak};
+use std::time::{Duration, Instant};
+
+use crate::channel;
+use crate::signal;
+
+// recv 轮询通道的最长间隔，决定了取消后最迟多久能返回
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "operation was cancelled")
+    }
+}
+
+impl Error for Cancelled {}
+
+// 可取消的接收操作失败的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecvError {
+    Cancelled,
+    Disconnected,
+}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecvError::Cancelled => write!(f, "receive was cancelled"),
+            RecvError::Disconnected => write!(f, "receiving on a closed channel"),
+        }
+    }
+}
+
+impl Error for RecvError {}
+
+struct NodeState {
+    cancelled: bool,
+    children: Vec<Weak<Node>>,
+}
+
+struct Node {
+    state: Mutex<NodeState>,
+    cond: Condvar,
+    deadline: Option<Instant>,
+}
+
+impl Node {
+    fn new(cancelled: bool, deadline: Option<Instant>) -> Node {
+        Node {
+            state: Mutex::new(NodeState { cancelled, children: vec![] }),
+            cond: Condvar::new(),
+            deadline,
+        }
+    }
+
+    fn cancel(&self) {
+        let children = {
+            let mut state = self.state.lock().unwrap();
+            if state.cancelled {
+                return;
+            }
+            state.cancelled = true;
+            self.cond.notify_all();
+            std::mem::take(&mut state.children)
+        };
+        // 在锁外递归取消子令牌
+        for child in children.iter().filter_map(Weak::upgrade) {
+            child.cancel();
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct CancellationToken {
+    node: Arc<Node>,
+}
+
+impl Default for CancellationToken {
+    fn default() -> Self {
+        CancellationToken::new()
+    }
+}
+
+impl fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CancellationToken")
+            .field("cancelled", &self.is_cancelled())
+            .field("deadline", &self.node.deadline)
+            .finish()
+    }
+}
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken { node: Arc::new(Node::new(false, None)) }
+    }
+
+    pub fn with_deadline(deadline: Instant) -> CancellationToken {
+        CancellationToken { node: Arc::new(Node::new(false, Some(deadline))) }
+    }
+
+    pub fn with_timeout(timeout: Duration) -> CancellationToken {
+        CancellationToken::with_deadline(Instant::now() + timeout)
+    }
+
+    // 派生子令牌，继承父令牌的截止时间
+    pub fn child(&self) -> CancellationToken {
+        self.child_with_deadline(None)
+    }
+
+    // 派生子令牌，截止时间取父令牌与 timeout 中较早的一个
+    pub fn child_with_timeout(&self, timeout: Duration) -> CancellationToken {
+        self.child_with_deadline(Some(Instant::now() + timeout))
+    }
+
+    fn child_with_deadline(&self, deadline: Option<Instant>) -> CancellationToken {
+        let deadline = match (self.node.deadline, deadline) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        let mut state = self.node.state.lock().unwrap();
+        let node = Arc::new(Node::new(state.cancelled, deadline));
+        if !state.cancelled {
+            // 顺便清理已经 drop 的子令牌
+            state.children.retain(|c| c.strong_count() > 0);
+            state.children.push(Arc::downgrade(&node));
+        }
+        CancellationToken { node }
+    }
+
+    pub fn cancel(&self) {
+        self.node.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.node.state.lock().unwrap().cancelled || self.deadline_passed()
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.node.deadline
+    }
+
+    fn deadline_passed(&self) -> bool {
+        self.node.deadline.is_some_and(|d| Instant::now() >= d)
+    }
+
+    // 便于配合 ? 使用：if 已取消 { return Err(Cancelled) }
+    pub fn check(&self) -> Result<(), Cancelled> {
+        if self.is_cancelled() {
+            Err(Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    // 等待到 until（None 表示一直等），期间被取消则立即返回 true
+    fn wait_until(&self, until: Option<Instant>) -> bool {
+        let mut state = self.node.state.lock().unwrap();
+        loop {
+            if state.cancelled || self.deadline_passed() {
+                return true;
+            }
+            let wake = match (until, self.node.deadline) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+            match wake {
+                None => state = self.node.cond.wait(state).unwrap(),
+                Some(wake) => {
+                    let now = Instant::now();
+                    if until.is_some_and(|u| now >= u) {
+                        return false;
+                    }
+                    state = self.node.cond.wait_timeout(state, wake.saturating_duration_since(now)).unwrap().0;
+                }
+            }
+        }
+    }
+
+    // 阻塞直到被取消或到达截止时间
+    pub fn wait_cancelled(&self) {
+        self.wait_until(None);
+    }
+
+    // 可被取消的 thread::sleep
+    pub fn sleep(&self, duration: Duration) -> Result<(), Cancelled> {
+        if self.wait_until(Some(Instant::now() + duration)) {
+            Err(Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    // 可被取消的接收，同时支持 std::sync::mpsc 和 crate::channel 的 Receiver
+    pub fn recv<T, R: TimedReceiver<T>>(&self, rx: &R) -> Result<T, RecvError> {
+        loop {
+            if self.is_cancelled() {
+                return Err(RecvError::Cancelled);
+            }
+            let slice = match self.node.deadline {
+                Some(d) => POLL_INTERVAL.min(d.saturating_duration_since(Instant::now())),
+                None => POLL_INTERVAL,
+            };
+            match rx.recv_for(slice) {
+                Ok(Some(value)) => return Ok(value),
+                Ok(None) => continue,
+                Err(Disconnected) => return Err(RecvError::Disconnected),
+            }
+        }
+    }
+}
+
+pub struct Disconnected;
+
+// 能够带超时接收消息的通道：Ok(None) 表示超时，Err 表示已断开
+pub trait TimedReceiver<T> {
+    fn recv_for(&self, timeout: Duration) -> Result<Option<T>, Disconnected>;
+}
+
+impl<T> TimedReceiver<T> for mpsc::Receiver<T> {
+    fn recv_for(&self, timeout: Duration) -> Result<Option<T>, Disconnected> {
+        match self.recv_timeout(timeout) {
+            Ok(value) => Ok(Some(value)),
+            Err(mpsc::RecvTimeoutError::Timeout) => Ok(None),
+            Err(mpsc::RecvTimeoutError::Disconnected) => Err(Disconnected),
+        }
+    }
+}
+
+impl<T> TimedReceiver<T> for channel::Receiver<T> {
+    fn recv_for(&self, timeout: Duration) -> Result<Option<T>, Disconnected> {
+        match self.recv_timeout(timeout) {
+            Ok(value) => Ok(Some(value)),
+            Err(channel::RecvTimeoutError::Timeout) => Ok(None),
+            Err(channel::RecvTimeoutError::Disconnected) => Err(Disconnected),
+        }
+    }
+}
+
+// 等待 Ctrl-C 的令牌。只保存 Weak，不会让已经没人用的令牌一直存活
+static CTRL_C_TOKENS: Mutex<Vec<Weak<Node>>> = Mutex::new(Vec::new());
+// 是否已经有一次 Ctrl-C 取消过令牌
+static CTRL_C_FIRED: AtomicBool = AtomicBool::new(false);
+
+// 按下 Ctrl-C（SIGINT）时取消 token。可以对多个令牌调用，它们会一起被取消。
+// 信号通过 signal.rs 订阅，不会替换 supervisor.rs 的处理。
+// 订阅之后 SIGINT 不再直接终止进程，所以和大多数命令行程序一样：第一次 Ctrl-C 取消令牌，
+// 之后再按 Ctrl-C 时如果没有新登记的令牌可以取消（程序还没停下来），就以 130（128 + SIGINT）退出。
+pub fn cancel_on_ctrl_c(token: &CancellationToken) {
+    static SUBSCRIBED: Once = Once::new();
+    SUBSCRIBED.call_once(|| {
+        signal::subscribe(libc::SIGINT, |_| {
+            let tokens = std::mem::take(&mut *CTRL_C_TOKENS.lock().unwrap());
+            let live: Vec<Arc<Node>> = tokens.iter().filter_map(Weak::upgrade).collect();
+            if live.is_empty() && CTRL_C_FIRED.load(Ordering::SeqCst) {
+                process::exit(128 + libc::SIGINT);
+            }
+            for node in live {
+                node.cancel();
+                CTRL_C_FIRED.store(true, Ordering::SeqCst);
+            }
+        })
+    });
+    let mut tokens = CTRL_C_TOKENS.lock().unwrap();
+    tokens.retain(|t| t.strong_count() > 0);
+    tokens.push(Arc::downgrade(&token.node));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn cancelling_parent_cancels_children() {
+        let parent = CancellationToken::new();
+        let child = parent.child();
+        let grandchild = child.child();
+        let clone = parent.clone();
+        child.cancel();
+        assert!(grandchild.is_cancelled());
+        assert!(!parent.is_cancelled());
+        clone.cancel();
+        assert!(parent.is_cancelled());
+        assert!(parent.child().is_cancelled()); // 已取消的令牌派生出的子令牌也是已取消的
+    }
+
+    #[test]
+    fn deadline_expires() {
+        let token = CancellationToken::with_timeout(Duration::from_millis(30));
+        let child = token.child_with_timeout(Duration::from_secs(60));
+        assert!(!child.is_cancelled());
+        let started = Instant::now();
+        assert_eq!(child.sleep(Duration::from_secs(5)), Err(Cancelled));
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(token.is_cancelled());
+    }
+
+    #[test]
+    fn sleeping_workers_stop_promptly() {
+        let token = CancellationToken::new();
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let token = token.child();
+                thread::spawn(move || {
+                    let mut rounds = 0;
+                    while token.sleep(Duration::from_millis(1000)).is_ok() {
+                        rounds += 1;
+                    }
+                    rounds
+                })
+            })
+            .collect();
+        thread::sleep(Duration::from_millis(20));
+        let started = Instant::now();
+        token.cancel();
+        for w in workers {
+            assert_eq!(w.join().unwrap(), 0);
+        }
+        assert!(started.elapsed() < Duration::from_millis(500));
+    }
+
+    #[test]
+    fn recv_is_cancellable() {
+        let (tx, rx) = mpsc::channel::<i32>();
+        let token = CancellationToken::new();
+        tx.send(1).unwrap();
+        assert_eq!(token.recv(&rx), Ok(1));
+
+        let canceller = token.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            canceller.cancel();
+        });
+        assert_eq!(token.recv(&rx), Err(RecvError::Cancelled));
+
+        let (tx, rx) = channel::bounded::<i32>(1);
+        drop(tx);
+        assert_eq!(CancellationToken::new().recv(&rx), Err(RecvError::Disconnected));
+    }
+
+    // 只在下面的测试里作为子进程运行
+    #[test]
+    #[ignore]
+    fn ctrl_c_child() {
+        if std::env::var_os("MY_PROJECT_CTRL_C_CHILD").is_none() {
+            return; // 直接用 --ignored 运行时什么都不做
+        }
+        let (first, second) = (CancellationToken::new(), CancellationToken::new());
+        let child = second.child();
+        cancel_on_ctrl_c(&first);
+        cancel_on_ctrl_c(&second);
+        unsafe { libc::raise(libc::SIGINT) };
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !(first.is_cancelled() && second.is_cancelled()) && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert!(first.is_cancelled() && second.is_cancelled() && child.is_cancelled());
+        println!("all tokens cancelled");
+        // 令牌都已经取消过了，第二次 Ctrl-C 直接退出进程
+        unsafe { libc::raise(libc::SIGINT) };
+        thread::sleep(Duration::from_secs(5));
+        println!("still running");
+    }
+
+    // SIGINT 对整个进程生效，放在子进程里测试，不影响同一进程里的其他测试
+    #[test]
+    fn ctrl_c_cancels_tokens_then_exits_on_the_second_press() {
+        let output = std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["--ignored", "--exact", "cancel::tests::ctrl_c_child", "--test-threads=1", "--nocapture"])
+            .env("MY_PROJECT_CTRL_C_CHILD", "1")
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("all tokens cancelled"), "{}{}", stdout, String::from_utf8_lossy(&output.stderr));
+        assert!(!stdout.contains("still running"), "{}", stdout);
+        assert_eq!(output.status.code(), Some(130));
+    }
+}
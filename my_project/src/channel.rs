@@ -0,0 +1,532 @@
+#![allow(dead_code)]
+
+// 有界通道（bounded channel）
+// threads.rs 使用的 mpsc::channel 是无界的，生产者比消费者快时消息会无限堆积，耗尽内存。
+// 有界通道的队列满了以后，send 会阻塞（或返回错误），从而把压力传回生产者（backpressure）。
+// 这里的 Sender 和 Receiver 都可以 clone，即多生产者多消费者（MPMC）；
+// 所有 Receiver 都 drop 后 send 失败，所有 Sender 都 drop 且队列为空后 recv 失败（断开检测）。
+// 另外提供 select! 宏，同时等待多个 Receiver，并可以设置超时。
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+// 错误类型与 std::sync::mpsc 中的同名类型保持一致，发送失败时把消息交还给调用者
+#[derive(Debug, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TrySendError<T> {
+    Full(T),
+    Disconnected(T),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SendTimeoutError<T> {
+    Timeout(T),
+    Disconnected(T),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecvError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryRecvError {
+    Empty,
+    Disconnected,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecvTimeoutError {
+    Timeout,
+    Disconnected,
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "sending on a disconnected channel")
+    }
+}
+
+impl<T> fmt::Display for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TrySendError::Full(_) => write!(f, "sending on a full channel"),
+            TrySendError::Disconnected(_) => write!(f, "sending on a disconnected channel"),
+        }
+    }
+}
+
+impl<T> fmt::Display for SendTimeoutError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SendTimeoutError::Timeout(_) => write!(f, "timed out waiting on send operation"),
+            SendTimeoutError::Disconnected(_) => write!(f, "sending on a disconnected channel"),
+        }
+    }
+}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "receiving on a closed channel")
+    }
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => write!(f, "receiving on an empty channel"),
+            TryRecvError::Disconnected => write!(f, "receiving on a closed channel"),
+        }
+    }
+}
+
+impl fmt::Display for RecvTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecvTimeoutError::Timeout => write!(f, "timed out waiting on channel"),
+            RecvTimeoutError::Disconnected => write!(f, "channel is empty and sending half is closed"),
+        }
+    }
+}
+
+impl<T: fmt::Debug> Error for SendError<T> {}
+impl<T: fmt::Debug> Error for TrySendError<T> {}
+impl<T: fmt::Debug> Error for SendTimeoutError<T> {}
+impl Error for RecvError {}
+impl Error for TryRecvError {}
+impl Error for RecvTimeoutError {}
+
+// select! 等待时使用的信号：任何一个被等待的通道有变化都会把它置位
+pub struct Signal {
+    fired: Mutex<bool>,
+    cond: Condvar,
+}
+
+impl Signal {
+    fn new() -> Signal {
+        Signal { fired: Mutex::new(false), cond: Condvar::new() }
+    }
+
+    fn fire(&self) {
+        *self.fired.lock().unwrap() = true;
+        self.cond.notify_all();
+    }
+
+    // 等待被置位，超过 deadline 返回 false
+    fn wait(&self, deadline: Option<Instant>) -> bool {
+        let mut fired = self.fired.lock().unwrap();
+        while !*fired {
+            match deadline {
+                None => fired = self.cond.wait(fired).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return false;
+                    }
+                    fired = self.cond.wait_timeout(fired, deadline - now).unwrap().0;
+                }
+            }
+        }
+        true
+    }
+}
+
+struct State<T> {
+    queue: VecDeque<T>,
+    senders: usize,
+    receivers: usize,
+    selectors: Vec<Arc<Signal>>,
+}
+
+struct Inner<T> {
+    state: Mutex<State<T>>,
+    cap: usize,
+    not_empty: Condvar,
+    not_full: Condvar,
+}
+
+impl<T> Inner<T> {
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap()
+    }
+}
+
+impl<T> State<T> {
+    // 通知正在 select 的线程重新检查
+    fn wake_selectors(&self) {
+        for signal in &self.selectors {
+            signal.fire();
+        }
+    }
+}
+
+pub struct Sender<T> {
+    inner: Arc<Inner<T>>,
+}
+
+pub struct Receiver<T> {
+    inner: Arc<Inner<T>>,
+}
+
+// 创建容量为 cap 的通道，cap 必须大于 0
+pub fn bounded<T>(cap: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(cap > 0, "channel capacity must be positive");
+    let inner = Arc::new(Inner {
+        state: Mutex::new(State {
+            queue: VecDeque::with_capacity(cap),
+            senders: 1,
+            receivers: 1,
+            selectors: vec![],
+        }),
+        cap,
+        not_empty: Condvar::new(),
+        not_full: Condvar::new(),
+    });
+    (Sender { inner: Arc::clone(&inner) }, Receiver { inner })
+}
+
+impl<T> Sender<T> {
+    // 队列满时阻塞，直到有空位或者所有 Receiver 都已 drop
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        self.send_deadline(value, None).map_err(|e| match e {
+            SendTimeoutError::Disconnected(v) | SendTimeoutError::Timeout(v) => SendError(v),
+        })
+    }
+
+    // 不阻塞，队列满时立即返回 Full
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        let mut state = self.inner.lock();
+        if state.receivers == 0 {
+            return Err(TrySendError::Disconnected(value));
+        }
+        if state.queue.len() >= self.inner.cap {
+            return Err(TrySendError::Full(value));
+        }
+        self.push(&mut state, value);
+        Ok(())
+    }
+
+    // 最多阻塞 timeout
+    pub fn send_timeout(&self, value: T, timeout: Duration) -> Result<(), SendTimeoutError<T>> {
+        self.send_deadline(value, Some(Instant::now() + timeout))
+    }
+
+    fn send_deadline(&self, value: T, deadline: Option<Instant>) -> Result<(), SendTimeoutError<T>> {
+        let mut state = self.inner.lock();
+        loop {
+            if state.receivers == 0 {
+                return Err(SendTimeoutError::Disconnected(value));
+            }
+            if state.queue.len() < self.inner.cap {
+                self.push(&mut state, value);
+                return Ok(());
+            }
+            state = match deadline {
+                None => self.inner.not_full.wait(state).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(SendTimeoutError::Timeout(value));
+                    }
+                    self.inner.not_full.wait_timeout(state, deadline - now).unwrap().0
+                }
+            };
+        }
+    }
+
+    fn push(&self, state: &mut State<T>, value: T) {
+        state.queue.push_back(value);
+        self.inner.not_empty.notify_one();
+        state.wake_selectors();
+    }
+
+    // 是否已经没有任何 Receiver
+    pub fn is_disconnected(&self) -> bool {
+        self.inner.lock().receivers == 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.inner.cap
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.inner.lock().senders += 1;
+        Sender { inner: Arc::clone(&self.inner) }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.inner.lock();
+        state.senders -= 1;
+        if state.senders == 0 {
+            // 唤醒所有等待中的接收者，让它们发现通道已断开
+            self.inner.not_empty.notify_all();
+            state.wake_selectors();
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    // 队列空时阻塞，直到有消息或者所有 Sender 都已 drop
+    pub fn recv(&self) -> Result<T, RecvError> {
+        self.recv_deadline(None).map_err(|_| RecvError)
+    }
+
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        let mut state = self.inner.lock();
+        match self.pop(&mut state) {
+            Some(value) => Ok(value),
+            None if state.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        self.recv_deadline(Some(Instant::now() + timeout))
+    }
+
+    fn recv_deadline(&self, deadline: Option<Instant>) -> Result<T, RecvTimeoutError> {
+        let mut state = self.inner.lock();
+        loop {
+            if let Some(value) = self.pop(&mut state) {
+                return Ok(value);
+            }
+            if state.senders == 0 {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            state = match deadline {
+                None => self.inner.not_empty.wait(state).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(RecvTimeoutError::Timeout);
+                    }
+                    self.inner.not_empty.wait_timeout(state, deadline - now).unwrap().0
+                }
+            };
+        }
+    }
+
+    fn pop(&self, state: &mut State<T>) -> Option<T> {
+        let value = state.queue.pop_front()?;
+        self.inner.not_full.notify_one();
+        Some(value)
+    }
+
+    // 阻塞迭代，直到通道断开
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        std::iter::from_fn(move || self.recv().ok())
+    }
+
+    // 是否已经没有任何 Sender（队列里可能还有消息）
+    pub fn is_disconnected(&self) -> bool {
+        self.inner.lock().senders == 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.inner.cap
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        self.inner.lock().receivers += 1;
+        Receiver { inner: Arc::clone(&self.inner) }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.inner.lock();
+        state.receivers -= 1;
+        if state.receivers == 0 {
+            self.inner.not_full.notify_all();
+        }
+    }
+}
+
+// select! 需要的类型无关操作：检查是否就绪、注册 / 注销等待信号
+pub trait Selectable {
+    fn is_ready(&self) -> bool;
+    fn register(&self, signal: &Arc<Signal>);
+    fn unregister(&self, signal: &Arc<Signal>);
+}
+
+impl<T> Selectable for Receiver<T> {
+    // 有消息可读，或者已经断开（recv 会立即返回错误），都算就绪
+    fn is_ready(&self) -> bool {
+        let state = self.inner.lock();
+        !state.queue.is_empty() || state.senders == 0
+    }
+
+    fn register(&self, signal: &Arc<Signal>) {
+        self.inner.lock().selectors.push(Arc::clone(signal));
+    }
+
+    fn unregister(&self, signal: &Arc<Signal>) {
+        self.inner.lock().selectors.retain(|s| !Arc::ptr_eq(s, signal));
+    }
+}
+
+// 阻塞直到 handles 中至少有一个就绪；到达 deadline 仍没有就绪的则返回 false。
+// 先注册信号再检查状态，这样在检查之后到来的消息一定会置位信号，不会丢失唤醒。
+pub fn wait_any(handles: &[&dyn Selectable], deadline: Option<Instant>) -> bool {
+    let signal = Arc::new(Signal::new());
+    for h in handles {
+        h.register(&signal);
+    }
+    let ready = handles.iter().any(|h| h.is_ready()) || signal.wait(deadline);
+    for h in handles {
+        h.unregister(&signal);
+    }
+    ready
+}
+
+// 同时等待多个 Receiver，执行第一个就绪分支；可选的 timeout 分支在超时后执行：
+// select! {
+//     recv(rx1) -> msg => println!("rx1: {:?}", msg),
+//     recv(rx2) -> msg => println!("rx2: {:?}", msg),
+//     timeout(Duration::from_secs(1)) => println!("timed out"),
+// }
+// msg 的类型是 Result<T, RecvError>，通道断开时为 Err。排在前面的分支优先。
+#[macro_export]
+macro_rules! select {
+    ($(recv($rx:expr) -> $msg:pat => $body:expr),+ , timeout($dur:expr) => $timeout:expr $(,)?) => {
+        $crate::select!(@impl [$(($rx, $msg, $body))+] Some(std::time::Instant::now() + $dur); $timeout)
+    };
+    ($(recv($rx:expr) -> $msg:pat => $body:expr),+ $(,)?) => {
+        $crate::select!(@impl [$(($rx, $msg, $body))+] None)
+    };
+    (@impl [$(($rx:expr, $msg:pat, $body:expr))+] $deadline:expr $(; $timeout:expr)?) => {{
+        let deadline: Option<std::time::Instant> = $deadline;
+        'select: loop {
+            $(
+                match $rx.try_recv() {
+                    Err($crate::channel::TryRecvError::Empty) => {}
+                    result => {
+                        let $msg = result.map_err(|_| $crate::channel::RecvError);
+                        break 'select $body;
+                    }
+                }
+            )+
+            // 其他消费者可能抢先取走消息，所以被唤醒后回到循环开头重新尝试
+            if !$crate::channel::wait_any(&[$(&$rx as &dyn $crate::channel::Selectable),+], deadline) {
+                $(break 'select $timeout;)?
+            }
+        }
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn full_channel_applies_backpressure() {
+        let (tx, rx) = bounded(2);
+        tx.send(1).unwrap();
+        tx.try_send(2).unwrap();
+        assert_eq!(tx.try_send(3), Err(TrySendError::Full(3)));
+        assert_eq!(tx.send_timeout(3, Duration::from_millis(20)), Err(SendTimeoutError::Timeout(3)));
+
+        let producer = thread::spawn(move || tx.send(3)); // 阻塞到消费者取走一个
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(rx.recv(), Ok(1));
+        producer.join().unwrap().unwrap();
+        assert_eq!(rx.iter().collect::<Vec<_>>(), vec![2, 3]);
+    }
+
+    #[test]
+    fn detects_disconnect() {
+        let (tx, rx) = bounded::<i32>(1);
+        assert_eq!(rx.recv_timeout(Duration::from_millis(10)), Err(RecvTimeoutError::Timeout));
+        drop(tx);
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+
+        let (tx, rx) = bounded(1);
+        drop(rx);
+        assert_eq!(tx.send(5), Err(SendError(5)));
+        assert!(tx.is_disconnected());
+    }
+
+    #[test]
+    fn multiple_producers_and_consumers() {
+        let (tx, rx) = bounded(4);
+        let producers: Vec<_> = (0..4u64)
+            .map(|p| {
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    for i in 0..1000 {
+                        tx.send(p * 1000 + i).unwrap();
+                    }
+                })
+            })
+            .collect();
+        drop(tx);
+        let consumers: Vec<_> = (0..3)
+            .map(|_| {
+                let rx = rx.clone();
+                thread::spawn(move || rx.iter().sum::<u64>())
+            })
+            .collect();
+        drop(rx);
+        for p in producers {
+            p.join().unwrap();
+        }
+        let total: u64 = consumers.into_iter().map(|c| c.join().unwrap()).sum();
+        assert_eq!(total, (0..4000).sum());
+    }
+
+    #[test]
+    fn select_waits_on_several_receivers() {
+        let (tx1, rx1) = bounded::<i32>(1);
+        let (tx2, rx2) = bounded::<&str>(1);
+        let producer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            tx2.send("hello").unwrap();
+        });
+        let got = crate::select! {
+            recv(rx1) -> msg => format!("rx1 {:?}", msg),
+            recv(rx2) -> msg => format!("rx2 {:?}", msg),
+        };
+        assert_eq!(got, "rx2 Ok(\"hello\")");
+
+        let timed_out = crate::select! {
+            recv(rx1) -> _msg => false,
+            timeout(Duration::from_millis(10)) => true,
+        };
+        assert!(timed_out);
+
+        // tx2 随线程结束被 drop，断开的 rx2 立即就绪
+        producer.join().unwrap();
+        let msg = crate::select! {
+            recv(rx1) -> msg => msg.map(|n| n.to_string()),
+            recv(rx2) -> msg => msg.map(|s| s.to_string()),
+            timeout(Duration::from_secs(5)) => Ok("timed out".to_string()),
+        };
+        assert_eq!(msg, Err(RecvError));
+        drop(tx1);
+    }
+}
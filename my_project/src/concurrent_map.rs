@@ -0,0 +1,216 @@
+#![allow(dead_code)]
+
+// 分片的并发 HashMap
+// standards.rs 里的 HashMap 只在单线程中使用；threads.rs 想在线程间汇总数据时只能把结果都发到一个通道里，
+// 由唯一的接收者统计，接收者很容易成为瓶颈。
+// ConcurrentMap 把数据按 key 的哈希值分到 N 个分片（shard），每个分片是一个 RwLock<HashMap>：
+// 不同分片上的读写互不影响，同一分片上可以多个线程同时读。
+// 因为锁在方法返回时就释放了，get 返回的是值的克隆；需要读写已有的值时用 upsert / get_with 传入闭包。
+// snapshot 逐个分片复制数据，每个分片内部一致，但整体不是同一时刻的快照。
+// 也可以让每个线程先在自己的 HashMap 里计数，最后用 merge 一次性并入，减少加锁次数。
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash};
+use std::sync::RwLock;
+
+use crate::mapreduce::default_workers;
+
+pub struct ConcurrentMap<K, V> {
+    shards: Vec<RwLock<HashMap<K, V>>>,
+    hasher: RandomState,
+}
+
+impl<K: Hash + Eq, V> Default for ConcurrentMap<K, V> {
+    fn default() -> Self {
+        ConcurrentMap::new()
+    }
+}
+
+impl<K: Hash + Eq, V> ConcurrentMap<K, V> {
+    // 分片数取 CPU 核数的 4 倍，向上取到 2 的幂
+    pub fn new() -> ConcurrentMap<K, V> {
+        ConcurrentMap::with_shards((default_workers() * 4).next_power_of_two())
+    }
+
+    pub fn with_shards(shards: usize) -> ConcurrentMap<K, V> {
+        ConcurrentMap {
+            shards: (0..shards.max(1)).map(|_| RwLock::new(HashMap::new())).collect(),
+            hasher: RandomState::new(),
+        }
+    }
+
+    fn shard<Q>(&self, key: &Q) -> &RwLock<HashMap<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        &self.shards[self.hasher.hash_one(key) as usize % self.shards.len()]
+    }
+
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        self.shard(&key).write().unwrap().insert(key, value)
+    }
+
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shard(key).write().unwrap().remove(key)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shard(key).read().unwrap().contains_key(key)
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        self.get_with(key, V::clone)
+    }
+
+    // 在读锁内访问值，避免克隆整个值
+    pub fn get_with<Q, R>(&self, key: &Q, f: impl FnOnce(&V) -> R) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shard(key).read().unwrap().get(key).map(f)
+    }
+
+    // entry 风格的更新：key 不存在时先用 default 插入，然后在写锁内对值调用 f
+    pub fn upsert<R>(&self, key: K, default: impl FnOnce() -> V, f: impl FnOnce(&mut V) -> R) -> R {
+        let mut shard = self.shard(&key).write().unwrap();
+        f(shard.entry(key).or_insert_with(default))
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|s| s.read().unwrap().len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|s| s.read().unwrap().is_empty())
+    }
+
+    // 逐个分片复制出所有键值对
+    pub fn snapshot(&self) -> Vec<(K, V)>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let mut entries = Vec::with_capacity(self.len());
+        for shard in &self.shards {
+            entries.extend(shard.read().unwrap().iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+        entries
+    }
+
+    // 在每个分片的读锁内依次访问键值对，f 里不能再访问这个 map 的写操作，否则会死锁
+    pub fn for_each(&self, mut f: impl FnMut(&K, &V)) {
+        for shard in &self.shards {
+            for (k, v) in shard.read().unwrap().iter() {
+                f(k, v);
+            }
+        }
+    }
+
+    // 把线程本地的计数并入：已有的 key 用 combine 合并，没有的直接插入。每个分片只加一次锁
+    pub fn merge(&self, local: HashMap<K, V>, combine: impl Fn(&mut V, V)) {
+        let mut buckets: Vec<Vec<(K, V)>> = (0..self.shards.len()).map(|_| vec![]).collect();
+        for (k, v) in local {
+            let i = self.hasher.hash_one(&k) as usize % self.shards.len();
+            buckets[i].push((k, v));
+        }
+        for (shard, bucket) in self.shards.iter().zip(buckets) {
+            if bucket.is_empty() {
+                continue;
+            }
+            let mut shard = shard.write().unwrap();
+            for (k, v) in bucket {
+                match shard.get_mut(&k) {
+                    Some(existing) => combine(existing, v),
+                    None => {
+                        shard.insert(k, v);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn into_hash_map(self) -> HashMap<K, V> {
+        self.shards.into_iter().flat_map(|s| s.into_inner().unwrap()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    const TEXT: &str = "the quick brown fox jumps over the lazy dog the end";
+
+    #[test]
+    fn basic_operations() {
+        let map = ConcurrentMap::with_shards(4);
+        assert_eq!(map.insert("a".to_string(), 1), None);
+        assert_eq!(map.insert("a".to_string(), 2), Some(1));
+        map.insert("b".to_string(), 3);
+        assert_eq!(map.get("a"), Some(2));
+        assert_eq!(map.get_with("b", |v| v * 10), Some(30));
+        assert!(map.contains_key("b"));
+        assert_eq!(map.remove("b"), Some(3));
+        assert_eq!(map.get("b"), None);
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.snapshot(), vec![("a".to_string(), 2)]);
+    }
+
+    #[test]
+    fn concurrent_word_count_with_upsert() {
+        let map = ConcurrentMap::new();
+        thread::scope(|s| {
+            for _ in 0..8 {
+                s.spawn(|| {
+                    for _ in 0..100 {
+                        for word in TEXT.split_whitespace() {
+                            map.upsert(word, || 0, |n| *n += 1);
+                        }
+                    }
+                });
+            }
+        });
+        let counts = map.into_hash_map();
+        assert_eq!(counts["the"], 8 * 100 * 3);
+        assert_eq!(counts["fox"], 8 * 100);
+        assert_eq!(counts.len(), 9);
+    }
+
+    #[test]
+    fn merging_thread_local_maps() {
+        let map = ConcurrentMap::with_shards(3);
+        map.insert("the", 1000);
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    let mut local = HashMap::new();
+                    for word in TEXT.split_whitespace() {
+                        *local.entry(word).or_insert(0) += 1;
+                    }
+                    map.merge(local, |total, n| *total += n);
+                });
+            }
+        });
+        assert_eq!(map.get("the"), Some(1000 + 4 * 3));
+        assert_eq!(map.get("dog"), Some(4));
+        let mut total = 0;
+        map.for_each(|_, n| total += n);
+        assert_eq!(total, 1000 + 4 * TEXT.split_whitespace().count());
+    }
+}
@@ -0,0 +1,294 @@
+#![allow(dead_code)]
+
+// 定长容器与关联类型
+// generic.rs 的 Container(i32, i32) 只能装两个 i32，Contains 的 first / last 也写死了返回 i32。
+// 这里用常量泛型（const generics）把它推广成 Container<T, N>：
+// - 最多装 N 个 T，元素直接存放在结构体内的数组里（在栈上），不做任何堆分配
+// - push / insert 在容器已满时返回 CapacityError，把值还给调用者，而不是 panic；
+//   pop / remove 在没有元素时返回 None
+// - Contains 只保留一个关联类型 Item，first / last 返回 Option<&Self::Item>，空容器也有合理的结果
+// - difference 对任何支持减法的数值类型都能用：i32、u8、f64……
+// 数组里只有前 len 个元素是初始化过的，所以用 MaybeUninit 存放，并自己实现 Drop 和 Clone。
+use std::error::Error;
+use std::fmt;
+use std::mem::{ManuallyDrop, MaybeUninit};
+use std::ops::{Deref, DerefMut, Sub};
+use std::ptr;
+use std::slice;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CapacityError<T> {
+    Full(T),               // 容器已满
+    OutOfBounds(usize, T), // insert 的下标大于 len
+}
+
+impl<T> CapacityError<T> {
+    // 取回没能放进去的值
+    pub fn into_inner(self) -> T {
+        match self {
+            CapacityError::Full(value) | CapacityError::OutOfBounds(_, value) => value,
+        }
+    }
+}
+
+impl<T> fmt::Display for CapacityError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CapacityError::Full(_) => write!(f, "container is full"),
+            CapacityError::OutOfBounds(index, _) => write!(f, "insertion index {} is out of bounds", index),
+        }
+    }
+}
+
+impl<T: fmt::Debug> Error for CapacityError<T> {}
+
+pub struct Container<T, const N: usize> {
+    items: [MaybeUninit<T>; N],
+    len: usize, // items[..len] 已初始化
+}
+
+impl<T, const N: usize> Container<T, N> {
+    pub const fn new() -> Container<T, N> {
+        Container { items: [const { MaybeUninit::uninit() }; N], len: 0 }
+    }
+
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    pub fn push(&mut self, value: T) -> Result<(), CapacityError<T>> {
+        if self.is_full() {
+            return Err(CapacityError::Full(value));
+        }
+        self.items[self.len].write(value);
+        self.len += 1;
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        self.len -= 1;
+        // items[len] 已初始化，len 减一后不会再被读取或 drop
+        Some(unsafe { self.items[self.len].assume_init_read() })
+    }
+
+    // 插入到 index 处，后面的元素依次后移
+    pub fn insert(&mut self, index: usize, value: T) -> Result<(), CapacityError<T>> {
+        if index > self.len {
+            return Err(CapacityError::OutOfBounds(index, value));
+        }
+        if self.is_full() {
+            return Err(CapacityError::Full(value));
+        }
+        unsafe {
+            let at = self.items.as_mut_ptr().add(index);
+            ptr::copy(at, at.add(1), self.len - index);
+            (*at).write(value);
+        }
+        self.len += 1;
+        Ok(())
+    }
+
+    // 删除 index 处的元素，后面的元素依次前移
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        if index >= self.len {
+            return None;
+        }
+        unsafe {
+            let at = self.items.as_mut_ptr().add(index);
+            let value = (*at).assume_init_read();
+            ptr::copy(at.add(1), at, self.len - index - 1);
+            self.len -= 1;
+            Some(value)
+        }
+    }
+
+    pub fn clear(&mut self) {
+        while self.pop().is_some() {}
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        unsafe { slice::from_raw_parts(self.items.as_ptr() as *const T, self.len) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        unsafe { slice::from_raw_parts_mut(self.items.as_mut_ptr() as *mut T, self.len) }
+    }
+}
+
+impl<T, const N: usize> Drop for Container<T, N> {
+    fn drop(&mut self) {
+        unsafe { ptr::drop_in_place(self.as_mut_slice()) }
+    }
+}
+
+impl<T, const N: usize> Default for Container<T, N> {
+    fn default() -> Container<T, N> {
+        Container::new()
+    }
+}
+
+impl<T: Clone, const N: usize> Clone for Container<T, N> {
+    fn clone(&self) -> Container<T, N> {
+        let mut copy = Container::new();
+        for item in self.iter() {
+            // 容量相同，不会满
+            let _ = copy.push(item.clone());
+        }
+        copy
+    }
+}
+
+impl<T: fmt::Debug, const N: usize> fmt::Debug for Container<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T: PartialEq, const N: usize> PartialEq for Container<T, N> {
+    fn eq(&self, other: &Container<T, N>) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+// 切片的方法（iter、len、下标访问、sort……）都可以直接用
+impl<T, const N: usize> Deref for Container<T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+
+impl<T, const N: usize> DerefMut for Container<T, N> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.as_mut_slice()
+    }
+}
+
+// Container::from([3, 10]) 得到装满的 Container<i32, 2>，相当于 generic.rs 的 Container(3, 10)
+impl<T, const N: usize> From<[T; N]> for Container<T, N> {
+    fn from(array: [T; N]) -> Container<T, N> {
+        let array = ManuallyDrop::new(array);
+        let mut container = Container::new();
+        // 元素的所有权逐个转移到 container 中，array 本身不再 drop
+        unsafe { ptr::copy_nonoverlapping(array.as_ptr(), container.items.as_mut_ptr() as *mut T, N) };
+        container.len = N;
+        container
+    }
+}
+
+// 检查某一项是否储存于容器中，并且能够获得容器的第一个或最后一个值
+pub trait Contains {
+    type Item;
+
+    fn contains(&self, item: &Self::Item) -> bool;
+    fn first(&self) -> Option<&Self::Item>;
+    fn last(&self) -> Option<&Self::Item>;
+}
+
+impl<T: PartialEq, const N: usize> Contains for Container<T, N> {
+    type Item = T;
+
+    fn contains(&self, item: &T) -> bool {
+        self.as_slice().contains(item)
+    }
+
+    fn first(&self) -> Option<&T> {
+        self.as_slice().first()
+    }
+
+    fn last(&self) -> Option<&T> {
+        self.as_slice().last()
+    }
+}
+
+// 和 generic.rs 一样是 last - first；容器为空时返回 None。
+// 无符号类型在 last < first 时同样会溢出
+pub fn difference<C>(container: &C) -> Option<C::Item>
+where
+    C: Contains,
+    C::Item: Copy + Sub<Output = C::Item>,
+{
+    Some(*container.last()? - *container.first()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem;
+    use std::rc::Rc;
+
+    #[test]
+    fn push_pop_insert_remove_report_capacity_errors() {
+        let mut c: Container<String, 3> = Container::new();
+        assert_eq!(c.pop(), None);
+        c.push("b".to_string()).unwrap();
+        c.insert(0, "a".to_string()).unwrap();
+        c.insert(2, "d".to_string()).unwrap();
+        assert_eq!(c.push("e".to_string()), Err(CapacityError::Full("e".to_string())));
+        assert_eq!(c.insert(1, "x".to_string()).unwrap_err().to_string(), "container is full");
+        assert_eq!(c.as_slice(), ["a", "b", "d"]);
+
+        assert_eq!(c.remove(3), None);
+        assert_eq!(c.remove(1).as_deref(), Some("b"));
+        assert_eq!(c.insert(5, "y".to_string()).unwrap_err().into_inner(), "y");
+        c.insert(1, "c".to_string()).unwrap();
+        assert_eq!(format!("{:?}", c), r#"["a", "c", "d"]"#);
+        assert_eq!(c.clone(), c);
+        assert_eq!(c.pop().as_deref(), Some("d"));
+        assert_eq!(c.len(), 2);
+    }
+
+    #[test]
+    fn stored_inline_and_drops_every_item_once() {
+        assert_eq!(mem::size_of::<Container<u64, 4>>(), mem::size_of::<[u64; 4]>() + mem::size_of::<usize>());
+
+        let item = Rc::new(());
+        {
+            let mut c: Container<Rc<()>, 8> = Container::new();
+            for _ in 0..5 {
+                c.push(Rc::clone(&item)).unwrap();
+            }
+            drop(c.remove(2));
+            c.insert(0, Rc::clone(&item)).unwrap();
+            let _copy = c.clone();
+            assert_eq!(Rc::strong_count(&item), 11);
+        }
+        assert_eq!(Rc::strong_count(&item), 1);
+
+        let from_array = Container::from([Rc::clone(&item), Rc::clone(&item)]);
+        assert!(from_array.is_full());
+        drop(from_array);
+        assert_eq!(Rc::strong_count(&item), 1);
+    }
+
+    #[test]
+    fn contains_and_difference_for_any_numeric_item() {
+        let pair = Container::from([3, 10]);
+        assert!(pair.contains(&3) && pair.contains(&10) && !pair.contains(&4));
+        assert_eq!((pair.first(), pair.last()), (Some(&3), Some(&10)));
+        assert_eq!(difference(&pair), Some(7));
+
+        let mut readings: Container<f64, 4> = Container::new();
+        assert_eq!(difference(&readings), None);
+        readings.push(1.5).unwrap();
+        readings.push(4.0).unwrap();
+        assert_eq!(difference(&readings), Some(2.5));
+        assert_eq!(difference(&Container::from([2u8, 200])), Some(198));
+    }
+}
@@ -0,0 +1,301 @@
+#![allow(dead_code)]
+
+// 崩溃报告
+// exceptions.rs 说 panic 会“打印一个错误消息”，这条消息只出现在终端上。程序在同事的机器上崩溃时，
+// 对方通常只会说“它崩了”，我们拿不到 panic 的位置和调用栈。
+// install 安装一个 panic 钩子（hook），panic 时：
+// 1. 收集线程名、panic 位置、panic 信息（payload）、调用栈、命令行参数和程序版本
+// 2. 在报告目录中写入同名的 .txt（给人看）和 .json（给工具处理）两个文件，文件名带时间戳
+// 3. 在终端上只打印一段简短友好的说明，告诉用户报告在哪里（按当前语言，见 i18n.rs）
+// 写文件失败时退回到把完整报告打印到 stderr，保证信息不会丢。
+// 钩子会保留之前安装的钩子。在 pool::run_catching 里发生的 panic 会由调用者处理（JobHandle 返回 Err、
+// 工作组汇总报告、actor 重启），不算崩溃，交给之前的钩子处理，不写报告。
+use std::backtrace::Backtrace;
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::panic::{self, PanicHookInfo};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::i18n::tr;
+use crate::pool::{panic_is_caught, panic_message};
+
+pub const APP_NAME: &str = env!("CARGO_PKG_NAME");
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// 同一秒内多次 panic 时用序号区分文件名
+static SEQUENCE: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone)]
+pub struct CrashReport {
+    pub timestamp: u64, // Unix 时间，秒
+    pub thread: String,
+    pub location: String,
+    pub message: String,
+    pub backtrace: String,
+    pub args: Vec<String>,
+    pub version: String,
+    pub pid: u32,
+}
+
+impl CrashReport {
+    pub fn from_panic(info: &PanicHookInfo) -> CrashReport {
+        CrashReport {
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()),
+            thread: thread::current().name().unwrap_or("<unnamed>").to_string(),
+            location: info.location().map_or_else(|| "<unknown>".to_string(), |l| l.to_string()),
+            message: panic_message(info.payload()),
+            // 报告就是为了调用栈，不管 RUST_BACKTRACE 有没有设置都捕获
+            backtrace: Backtrace::force_capture().to_string(),
+            args: env::args().collect(),
+            version: VERSION.to_string(),
+            pid: std::process::id(),
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "{} {} crash report", APP_NAME, self.version);
+        let _ = writeln!(out, "time:     {}", format_timestamp(self.timestamp, true));
+        let _ = writeln!(out, "pid:      {}", self.pid);
+        let _ = writeln!(out, "thread:   {}", self.thread);
+        let _ = writeln!(out, "location: {}", self.location);
+        let _ = writeln!(out, "message:  {}", self.message);
+        let _ = writeln!(out, "args:     {:?}", self.args);
+        let _ = write!(out, "\nbacktrace:\n{}", self.backtrace);
+        out
+    }
+
+    pub fn to_json(&self) -> String {
+        let args: Vec<String> = self.args.iter().map(|a| json_string(a)).collect();
+        format!(
+            "{{\n  \"app\": {},\n  \"version\": {},\n  \"timestamp\": {},\n  \"time\": {},\n  \"pid\": {},\n  \
+             \"thread\": {},\n  \"location\": {},\n  \"message\": {},\n  \"args\": [{}],\n  \"backtrace\": {}\n}}\n",
+            json_string(APP_NAME),
+            json_string(&self.version),
+            self.timestamp,
+            json_string(&format_timestamp(self.timestamp, true)),
+            self.pid,
+            json_string(&self.thread),
+            json_string(&self.location),
+            json_string(&self.message),
+            args.join(", "),
+            json_string(&self.backtrace),
+        )
+    }
+
+    // 写入 dir/crash-<时间>-<pid>-<序号>.txt 和 .json，返回 .txt 的路径
+    pub fn write_to(&self, dir: &Path) -> io::Result<PathBuf> {
+        fs::create_dir_all(dir)?;
+        let stem = format!(
+            "crash-{}-{}-{}",
+            format_timestamp(self.timestamp, false),
+            self.pid,
+            SEQUENCE.fetch_add(1, Ordering::SeqCst)
+        );
+        let text = dir.join(format!("{}.txt", stem));
+        fs::write(&text, self.to_text())?;
+        fs::write(dir.join(format!("{}.json", stem)), self.to_json())?;
+        Ok(text)
+    }
+}
+
+// 默认的报告目录，可以用环境变量 MY_PROJECT_CRASH_DIR 指定
+pub fn default_dir() -> PathBuf {
+    env::var_os("MY_PROJECT_CRASH_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| env::temp_dir().join(format!("{}-crashes", APP_NAME)))
+}
+
+pub fn install(dir: PathBuf) {
+    // 先加载消息目录。否则第一次 panic 如果发生在 messages() 初始化的过程中（例如目录文件读取出错），
+    // 钩子里的 tr! 会再次进入同一个 OnceLock，导致死锁
+    crate::i18n::messages();
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        // 会被捕获并交给调用者处理的 panic 不算崩溃
+        if panic_is_caught() {
+            previous(info);
+            return;
+        }
+        let report = CrashReport::from_panic(info);
+        match report.write_to(&dir) {
+            Ok(path) => {
+                let saved = tr!("crash.saved", app = APP_NAME, message = report.message, path = path.display());
+                eprintln!("\n{}", saved)
+            }
+            Err(e) => {
+                let failed = tr!("crash.write_failed", dir = dir.display(), error = e);
+                eprintln!("\n{}\n{}", report.to_text(), failed)
+            }
+        }
+    }));
+}
+
+// 时间戳转成 UTC 时间。readable 为 true 时是 2026-10-19 08:30:00 UTC，否则是适合做文件名的 20261019T083000Z
+fn format_timestamp(secs: u64, readable: bool) -> String {
+    let (days, rem) = ((secs / 86400) as i64, secs % 86400);
+    // 公历日期换算（Howard Hinnant 的 civil_from_days）
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    let (h, m, s) = (rem / 3600, rem / 60 % 60, rem % 60);
+    if readable {
+        format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC", year, month, day, h, m, s)
+    } else {
+        format!("{:04}{:02}{:02}T{:02}{:02}{:02}Z", year, month, day, h, m, s)
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn timestamps_and_json_escaping() {
+        assert_eq!(format_timestamp(0, true), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_timestamp(951_782_400 + 3661, false), "20000229T010101Z");
+        assert_eq!(format_timestamp(1_792_400_000, true), "2026-10-19 08:53:20 UTC");
+        assert_eq!(json_string("say \"hi\"\n\\ \u{1}"), r#""say \"hi\"\n\\ \u0001""#);
+    }
+
+    // 只在下面的测试里作为子进程运行：安装钩子后 panic
+    #[test]
+    #[ignore]
+    fn crashing_child() {
+        if env::var_os("MY_PROJECT_CRASH_DIR").is_none() {
+            return; // 直接用 --ignored 运行时什么都不做
+        }
+        install(default_dir());
+        thread::Builder::new()
+            .name("loader".to_string())
+            .spawn(|| panic!("config \"app.toml\" is broken"))
+            .unwrap()
+            .join()
+            .unwrap_err();
+    }
+
+    // 只在下面的测试里作为子进程运行：线程池任务的 panic 由 JobHandle 处理，之后才真正崩溃
+    #[test]
+    #[ignore]
+    fn handled_panic_child() {
+        if env::var_os("MY_PROJECT_CRASH_DIR").is_none() {
+            return;
+        }
+        install(default_dir());
+        let pool = crate::pool::ThreadPool::new(1);
+        let result = pool.submit(|| panic!("handled by the caller")).wait();
+        assert!(matches!(result, Err(crate::pool::JobError::Panicked(_))));
+        thread::spawn(|| panic!("really crashed")).join().unwrap_err();
+    }
+
+    #[test]
+    fn handled_panics_write_no_report() {
+        let dir = env::temp_dir().join(format!("my_project_crash_handled_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let output = Command::new(env::current_exe().unwrap())
+            .args(["--ignored", "--exact", "crash::tests::handled_panic_child", "--test-threads=1", "--nocapture"])
+            .env("MY_PROJECT_CRASH_DIR", &dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+        let files: Vec<PathBuf> = fs::read_dir(&dir).unwrap().map(|e| e.unwrap().path()).collect();
+        let reports: Vec<String> = files.iter().map(|f| fs::read_to_string(f).unwrap()).collect();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(files.len(), 2, "only the real crash is reported: {:?}", files);
+        for report in reports {
+            assert!(report.contains("really crashed") && !report.contains("handled by the caller"), "{}", report);
+        }
+    }
+
+    // 只在下面的测试里作为子进程运行：作用域线程的 panic 被 scope 重新抛出，没有人处理
+    #[test]
+    #[ignore]
+    fn scoped_crash_child() {
+        if env::var_os("MY_PROJECT_CRASH_DIR").is_none() {
+            return;
+        }
+        install(default_dir());
+        crate::scope::scope(|s| {
+            s.spawn(|| panic!("scoped worker crashed"));
+        });
+    }
+
+    #[test]
+    fn panics_rethrown_by_scope_are_reported() {
+        let dir = env::temp_dir().join(format!("my_project_crash_scoped_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let output = Command::new(env::current_exe().unwrap())
+            .args(["--ignored", "--exact", "crash::tests::scoped_crash_child", "--test-threads=1", "--nocapture"])
+            .env("MY_PROJECT_CRASH_DIR", &dir)
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+
+        let files: Vec<PathBuf> = fs::read_dir(&dir).map(|d| d.map(|e| e.unwrap().path()).collect()).unwrap_or_default();
+        let reports: Vec<String> = files.iter().map(|f| fs::read_to_string(f).unwrap()).collect();
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(reports.len(), 2, "{}", String::from_utf8_lossy(&output.stderr));
+        assert!(reports.iter().all(|r| r.contains("scoped worker crashed")), "{:?}", reports);
+    }
+
+    #[test]
+    fn panics_write_text_and_json_reports() {
+        let dir = env::temp_dir().join(format!("my_project_crash_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let output = Command::new(env::current_exe().unwrap())
+            .args(["--ignored", "--exact", "crash::tests::crashing_child", "--test-threads=1", "--nocapture"])
+            .env("MY_PROJECT_CRASH_DIR", &dir)
+            .env("LC_ALL", "C") // 检查的是英文提示
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("my_project crashed: config \"app.toml\" is broken"), "{}", stderr);
+        assert!(stderr.contains("A crash report was saved to"), "{}", stderr);
+
+        let mut files: Vec<PathBuf> = fs::read_dir(&dir).unwrap().map(|e| e.unwrap().path()).collect();
+        files.sort();
+        assert_eq!(files.len(), 2);
+        let json = fs::read_to_string(&files[0]).unwrap();
+        let text = fs::read_to_string(&files[1]).unwrap();
+        assert!(json.contains(r#""thread": "loader""#), "{}", json);
+        assert!(json.contains(r#""message": "config \"app.toml\" is broken""#), "{}", json);
+        assert!(json.contains(&format!(r#""version": "{}""#, VERSION)));
+        assert!(json.contains("crash::tests::crashing_child"), "args are recorded: {}", json);
+        assert!(text.contains("location: src/crash.rs") || text.contains("crash.rs:"), "{}", text);
+        assert!(text.contains("backtrace:"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,438 @@
+#![allow(dead_code)]
+
+// 死锁检测：记录加锁顺序的 TrackedMutex
+// 典型的死锁是转账：线程 1 先锁账户 A 再锁 B，线程 2 先锁 B 再锁 A，两边各拿着一把锁等对方。
+// 这种问题只在特定的时序下才出现，测试里很难复现。但只要程序在任何时候“先 A 后 B”和“先 B 后 A”都发生过，
+// 就存在死锁的可能，不需要真的撞上。
+// TrackedMutex 的接口和 std::sync::Mutex 一样（lock 返回 LockResult，有 try_lock、get_mut 和毒化），
+// 把类型名换掉，已有的 .lock().unwrap() 不用改。额外做了这些事：
+// - 每个线程记录自己当前持有哪些锁、分别在哪一行加的锁（#[track_caller]）
+// - 持有 A 时去锁 B，就在全局的加锁顺序图（lock-order graph）里加一条边 A -> B
+// - 加边之前检查图里是否已经有 B 到 A 的路径，有的话就构成环，即潜在的死锁，生成一份带调用位置的报告
+// 默认只记录报告并打印警告；调用 set_panic_on_inversion(true) 后，在 debug 构建中发现环会直接 panic，
+// 方便在测试里第一时间失败。release 构建中这个开关不起作用。
+// 图中的边随着 TrackedMutex 的 drop 一起删除，所以图的大小只和当前存活的锁有关；已经生成的报告会一直保留。
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::panic::Location;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{LockResult, Mutex, MutexGuard, OnceLock, PoisonError, TryLockError, TryLockResult};
+use std::thread;
+
+use crate::i18n::tr;
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+static PANIC_ON_INVERSION: AtomicBool = AtomicBool::new(false);
+
+type Site = &'static Location<'static>;
+
+thread_local! {
+    // 当前线程持有的锁：(锁的 id, 锁名, 加锁位置)
+    static HELD: RefCell<Vec<(usize, String, Site)>> = const { RefCell::new(Vec::new()) };
+}
+
+// 加锁顺序图中的一条边：某个线程持有 from 时锁了 to
+#[derive(Debug, Clone)]
+pub struct LockEdge {
+    pub from: String,
+    pub to: String,
+    pub from_site: Site,
+    pub to_site: Site,
+    pub thread: String,
+}
+
+impl fmt::Display for LockEdge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = tr!(
+            "deadlock.edge",
+            thread = self.thread,
+            to = self.to,
+            to_site = self.to_site,
+            from = self.from,
+            from_site = self.from_site
+        );
+        f.write_str(&text)
+    }
+}
+
+// 一个加锁顺序的环，按边的顺序排列，最后一条边回到第一条边的起点
+#[derive(Debug, Clone)]
+pub struct DeadlockReport {
+    pub cycle: Vec<LockEdge>,
+}
+
+impl fmt::Display for DeadlockReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names: Vec<&str> = self.cycle.iter().map(|e| e.from.as_str()).collect();
+        let cycle = format!("{} -> {}", names.join(" -> "), names[0]);
+        f.write_str(&tr!("deadlock.cycle", cycle = cycle))?;
+        for edge in &self.cycle {
+            write!(f, "\n  {}", edge)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Graph {
+    edges: HashMap<usize, HashMap<usize, LockEdge>>,
+    reports: Vec<DeadlockReport>,
+    reported: HashSet<Vec<usize>>, // 已报告过的环（排序后的锁 id），避免重复报告
+}
+
+impl Graph {
+    // 深度优先搜索 from 到 to 的路径，返回路径上的锁 id
+    fn path(&self, from: usize, to: usize) -> Option<Vec<usize>> {
+        let mut stack = vec![vec![from]];
+        let mut seen = HashSet::new();
+        while let Some(path) = stack.pop() {
+            let last = *path.last().unwrap();
+            if last == to {
+                return Some(path);
+            }
+            if !seen.insert(last) {
+                continue;
+            }
+            for next in self.edges.get(&last).into_iter().flat_map(|e| e.keys()) {
+                let mut longer = path.clone();
+                longer.push(*next);
+                stack.push(longer);
+            }
+        }
+        None
+    }
+
+    // 删除和这把锁相关的所有边
+    fn forget(&mut self, id: usize) {
+        self.edges.remove(&id);
+        for targets in self.edges.values_mut() {
+            targets.remove(&id);
+        }
+        self.edges.retain(|_, targets| !targets.is_empty());
+    }
+}
+
+fn graph() -> &'static Mutex<Graph> {
+    static GRAPH: OnceLock<Mutex<Graph>> = OnceLock::new();
+    GRAPH.get_or_init(|| Mutex::new(Graph::default()))
+}
+
+// 只在 debug 构建中生效
+pub fn set_panic_on_inversion(enabled: bool) {
+    PANIC_ON_INVERSION.store(enabled, Ordering::SeqCst);
+}
+
+// 到目前为止发现的所有潜在死锁
+pub fn potential_deadlocks() -> Vec<DeadlockReport> {
+    graph().lock().unwrap().reports.clone()
+}
+
+fn panic_on_inversion() -> bool {
+    cfg!(debug_assertions) && PANIC_ON_INVERSION.load(Ordering::SeqCst)
+}
+
+// 把当前持有的每一把锁到这把锁的边加入图中，返回新发现的环
+fn record_acquire(id: usize, name: &str, site: Site) -> Vec<DeadlockReport> {
+    let held: Vec<(usize, String, Site)> = HELD.with(|h| h.borrow().clone());
+    if held.iter().any(|(h, _, _)| *h == id) {
+        panic!("{}", tr!("deadlock.relock", name = name, site = site));
+    }
+    let thread = thread::current().name().unwrap_or("<unnamed>").to_string();
+    let mut found = vec![];
+    {
+        let mut graph = graph().lock().unwrap();
+        for (from, from_name, from_site) in held {
+            if graph.edges.get(&from).is_some_and(|e| e.contains_key(&id)) {
+                continue;
+            }
+            let edge = LockEdge { from: from_name, to: name.to_string(), from_site, to_site: site, thread: thread.clone() };
+            if let Some(path) = graph.path(id, from) {
+                // 图中已有 id -> ... -> from，再加 from -> id 就成环
+                let mut key = path.clone();
+                key.sort_unstable();
+                if graph.reported.insert(key) {
+                    let mut cycle: Vec<LockEdge> = path.windows(2).map(|w| graph.edges[&w[0]][&w[1]].clone()).collect();
+                    cycle.push(edge.clone());
+                    let report = DeadlockReport { cycle };
+                    graph.reports.push(report.clone());
+                    found.push(report);
+                }
+            }
+            graph.edges.entry(from).or_default().insert(id, edge);
+        }
+    }
+    found
+}
+
+// 在释放图的锁之后再调用，panic 时不会毒化（poison）全局的图
+fn report(found: Vec<DeadlockReport>) {
+    for report in found {
+        if panic_on_inversion() {
+            panic!("{}", report);
+        }
+        eprintln!("{}", tr!("deadlock.warning", report = report));
+    }
+}
+
+// 锁的 id；drop 时把这把锁从加锁顺序图中删除
+struct LockId(usize);
+
+impl Drop for LockId {
+    fn drop(&mut self) {
+        graph().lock().unwrap_or_else(|p| p.into_inner()).forget(self.0);
+    }
+}
+
+pub struct TrackedMutex<T> {
+    id: LockId,
+    name: String,
+    inner: Mutex<T>,
+}
+
+impl<T> TrackedMutex<T> {
+    pub fn new(value: T) -> TrackedMutex<T> {
+        let id = NEXT_ID.fetch_add(1, Ordering::SeqCst);
+        TrackedMutex { id: LockId(id), name: format!("mutex#{}", id), inner: Mutex::new(value) }
+    }
+
+    // 带名字的锁，报告里更容易看懂，例如账户名
+    pub fn named(name: &str, value: T) -> TrackedMutex<T> {
+        TrackedMutex { name: name.to_string(), ..TrackedMutex::new(value) }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // 在加锁（可能阻塞）之前检查加锁顺序
+    #[track_caller]
+    pub fn lock(&self) -> LockResult<TrackedGuard<'_, T>> {
+        let site = Location::caller();
+        report(record_acquire(self.id.0, &self.name, site));
+        match self.inner.lock() {
+            Ok(guard) => Ok(self.track(guard, site)),
+            Err(poisoned) => Err(PoisonError::new(self.track(poisoned.into_inner(), site))),
+        }
+    }
+
+    // try_lock 不会阻塞，也就不会死锁，只有拿到锁之后才记录加锁顺序
+    #[track_caller]
+    pub fn try_lock(&self) -> TryLockResult<TrackedGuard<'_, T>> {
+        let site = Location::caller();
+        let (guard, poisoned) = match self.inner.try_lock() {
+            Ok(guard) => (guard, false),
+            Err(TryLockError::Poisoned(poisoned)) => (poisoned.into_inner(), true),
+            Err(TryLockError::WouldBlock) => return Err(TryLockError::WouldBlock),
+        };
+        let found = record_acquire(self.id.0, &self.name, site);
+        let guard = self.track(guard, site);
+        if !found.is_empty() && panic_on_inversion() {
+            drop(guard); // 先解锁再 panic，免得毒化这把锁
+            report(found);
+            unreachable!();
+        }
+        report(found);
+        if poisoned {
+            Err(TryLockError::Poisoned(PoisonError::new(guard)))
+        } else {
+            Ok(guard)
+        }
+    }
+
+    fn track<'a>(&'a self, guard: MutexGuard<'a, T>, site: Site) -> TrackedGuard<'a, T> {
+        HELD.with(|h| h.borrow_mut().push((self.id.0, self.name.clone(), site)));
+        TrackedGuard { id: self.id.0, guard }
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        self.inner.is_poisoned()
+    }
+
+    // 有 &mut self 时不可能有其他线程持有锁，不需要记录
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        self.inner.get_mut()
+    }
+
+    pub fn into_inner(self) -> LockResult<T> {
+        self.inner.into_inner()
+    }
+}
+
+pub struct TrackedGuard<'a, T> {
+    id: usize,
+    guard: MutexGuard<'a, T>,
+}
+
+impl<T> Deref for TrackedGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for TrackedGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T> Drop for TrackedGuard<'_, T> {
+    fn drop(&mut self) {
+        // 锁不一定按加锁的逆序释放，按 id 找
+        HELD.with(|h| {
+            let mut held = h.borrow_mut();
+            if let Some(i) = held.iter().rposition(|(id, _, _)| *id == self.id) {
+                held.remove(i);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 检测结果和 panic 开关都是全局的，这些测试不能并行
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    struct Account {
+        name: &'static str,
+        balance: TrackedMutex<i64>,
+    }
+
+    fn account(name: &'static str, balance: i64) -> Account {
+        Account { name, balance: TrackedMutex::named(name, balance) }
+    }
+
+    // 经典的错误写法：总是先锁转出方
+    fn transfer(from: &Account, to: &Account, amount: i64) {
+        let mut a = from.balance.lock().unwrap();
+        let mut b = to.balance.lock().unwrap();
+        *a -= amount;
+        *b += amount;
+    }
+
+    fn reports_for(name: &str) -> Vec<DeadlockReport> {
+        potential_deadlocks().into_iter().filter(|r| r.cycle.iter().any(|e| e.from == name)).collect()
+    }
+
+    #[test]
+    fn inverted_lock_order_is_reported_without_deadlocking() {
+        let _serial = SERIAL.lock().unwrap_or_else(|p| p.into_inner());
+        let (alice, bob) = (account("alice", 100), account("bob", 100));
+        // 两次转账在同一个线程里先后进行，不会真的死锁，但顺序相反
+        transfer(&alice, &bob, 10);
+        transfer(&bob, &alice, 5);
+        assert_eq!((alice.balance.into_inner().unwrap(), bob.balance.into_inner().unwrap()), (95, 105));
+
+        let reports = reports_for(alice.name);
+        assert_eq!(reports.len(), 1);
+        let text = reports[0].to_string();
+        assert!(text.contains("alice -> bob -> alice") || text.contains("bob -> alice -> bob"), "{}", text);
+        assert!(text.contains("deadlock.rs"), "call sites are included: {}", text);
+    }
+
+    #[test]
+    fn consistent_order_and_longer_cycles() {
+        let _serial = SERIAL.lock().unwrap_or_else(|p| p.into_inner());
+        let (a, b, c) = (account("carol", 0), account("dave", 0), account("erin", 0));
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..100 {
+                        transfer(&a, &b, 1);
+                        transfer(&b, &c, 1);
+                    }
+                });
+            }
+        });
+        assert!(reports_for(a.name).is_empty());
+        transfer(&c, &a, 1); // carol -> dave -> erin -> carol
+        assert_eq!(reports_for(a.name)[0].cycle.len(), 3);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn debug_mode_panics_on_inversion() {
+        let _serial = SERIAL.lock().unwrap_or_else(|p| p.into_inner());
+        let (x, y) = (account("frank", 0), account("grace", 0));
+        transfer(&x, &y, 1);
+        set_panic_on_inversion(true);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| transfer(&y, &x, 1)));
+        set_panic_on_inversion(false);
+        let message = crate::pool::panic_message(&*result.unwrap_err());
+        assert!(message.contains("frank -> grace -> frank") || message.contains("grace -> frank -> grace"), "{}", message);
+    }
+
+    #[test]
+    fn std_mutex_api_try_lock_and_poisoning() {
+        let _serial = SERIAL.lock().unwrap_or_else(|p| p.into_inner());
+        let (mut x, y) = (TrackedMutex::named("henry", 1), TrackedMutex::named("iris", 2));
+        {
+            let _held = x.lock().unwrap();
+            assert!(matches!(x.try_lock(), Err(TryLockError::WouldBlock)));
+            *y.try_lock().unwrap() += 1; // henry -> iris
+        }
+        let _y = y.lock().unwrap();
+        drop(x.try_lock().unwrap()); // iris -> henry，try_lock 成功，同样记录
+        drop(_y);
+        assert_eq!(reports_for("henry").len(), 1);
+
+        let _ = thread::scope(|s| {
+            s.spawn(|| {
+                let _held = x.lock().unwrap();
+                panic!("poison henry");
+            })
+            .join()
+        });
+        assert!(x.is_poisoned());
+        assert_eq!(*x.lock().unwrap_or_else(PoisonError::into_inner), 1);
+        assert_eq!(*x.get_mut().unwrap_or_else(PoisonError::into_inner), 1);
+    }
+
+    // 按 std::sync::Mutex 写的计数器，只把 use 换成了 TrackedMutex，其余一字未改
+    mod drop_in {
+        use super::super::TrackedMutex as Mutex;
+        use std::sync::Arc;
+        use std::thread;
+
+        pub fn count_in_threads(n: usize) -> i32 {
+            let counter = Arc::new(Mutex::new(0));
+            let handles: Vec<_> = (0..n)
+                .map(|_| {
+                    let counter = Arc::clone(&counter);
+                    thread::spawn(move || {
+                        let mut num = counter.lock().unwrap();
+                        *num += 1;
+                    })
+                })
+                .collect();
+            for handle in handles {
+                handle.join().unwrap();
+            }
+            let total = *counter.lock().unwrap();
+            total
+        }
+    }
+
+    #[test]
+    fn drop_in_replacement_for_std_mutex() {
+        assert_eq!(drop_in::count_in_threads(10), 10);
+    }
+
+    #[test]
+    fn dropped_mutexes_leave_the_graph() {
+        let _serial = SERIAL.lock().unwrap_or_else(|p| p.into_inner());
+        let edges = || graph().lock().unwrap().edges.len();
+        let before = edges();
+        {
+            let (a, b) = (account("judy", 0), account("ken", 0));
+            transfer(&a, &b, 1);
+            assert_eq!(edges(), before + 1);
+        }
+        assert_eq!(edges(), before);
+    }
+}
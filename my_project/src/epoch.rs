@@ -0,0 +1,213 @@
+#![allow(dead_code)]
+
+// 基于纪元（epoch）的内存回收
+// 无锁数据结构把一个节点从链表上摘下来之后，别的线程可能刚刚读到这个节点的指针、正准备访问它，
+// 所以不能马上释放。epoch 回收的做法是：
+// 1. 线程访问共享指针之前先 pin()，登记“我正处在第 e 个纪元”，Guard 离开作用域时取消登记
+// 2. 摘下来的节点不立即释放，而是 retire：连同当时的全局纪元一起放进本线程的垃圾袋
+// 3. 只有当所有正在 pin 的线程都已经看到当前纪元时，全局纪元才能前进一格
+// 4. 在纪元 e 退休的节点，等全局纪元到了 e + 2 就一定没有线程还持有它的指针，可以释放
+// 同一个节点在被释放之前不会被重新分配，这也顺带避免了 ABA 问题。
+// 登记表和孤儿垃圾（线程退出时没来得及释放的节点）用 Mutex 保护，只在注册线程和回收时使用，
+// pin / retire 以及数据结构本身的操作都不加锁。
+use std::cell::{Cell, RefCell};
+use std::marker::PhantomData;
+use std::sync::atomic::{fence, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+// 垃圾袋攒到这么多个节点就尝试回收一次
+const COLLECT_THRESHOLD: usize = 64;
+
+static EPOCH: AtomicUsize = AtomicUsize::new(0);
+static PARTICIPANTS: Mutex<Vec<Arc<Participant>>> = Mutex::new(Vec::new());
+static ORPHANS: Mutex<Vec<Retired>> = Mutex::new(Vec::new());
+
+struct Participant {
+    // 0 表示没有 pin；否则是 (纪元 << 1) | 1
+    state: AtomicUsize,
+}
+
+struct Retired {
+    ptr: *mut u8,
+    drop_fn: unsafe fn(*mut u8),
+    epoch: usize,
+}
+
+// Retired 只在线程退出时转交给 ORPHANS，此后由别的线程释放；指向的对象本身要求 Send
+unsafe impl Send for Retired {}
+
+unsafe fn drop_box<T>(ptr: *mut u8) {
+    drop(Box::from_raw(ptr as *mut T));
+}
+
+struct Local {
+    participant: Arc<Participant>,
+    pins: Cell<usize>, // 支持嵌套 pin
+    bag: RefCell<Vec<Retired>>, // 按纪元从旧到新排列
+    retired_since_collect: Cell<usize>,
+}
+
+impl Local {
+    fn new() -> Local {
+        let participant = Arc::new(Participant { state: AtomicUsize::new(0) });
+        PARTICIPANTS.lock().unwrap().push(Arc::clone(&participant));
+        Local { participant, pins: Cell::new(0), bag: RefCell::new(vec![]), retired_since_collect: Cell::new(0) }
+    }
+}
+
+impl Drop for Local {
+    fn drop(&mut self) {
+        PARTICIPANTS.lock().unwrap().retain(|p| !Arc::ptr_eq(p, &self.participant));
+        let mut orphans = ORPHANS.lock().unwrap();
+        orphans.append(&mut self.bag.borrow_mut());
+        orphans.sort_by_key(|r| r.epoch);
+    }
+}
+
+thread_local! {
+    static LOCAL: Local = Local::new();
+}
+
+// pin 的凭证。持有期间从无锁结构里读到的节点不会被释放；不能跨线程传递
+pub struct Guard {
+    _not_send: PhantomData<*const ()>,
+}
+
+pub fn pin() -> Guard {
+    LOCAL.with(|local| {
+        let pins = local.pins.get();
+        if pins == 0 {
+            let epoch = EPOCH.load(Ordering::SeqCst);
+            local.participant.state.store((epoch << 1) | 1, Ordering::SeqCst);
+            // 保证之后读共享指针的操作不会被重排到登记之前
+            fence(Ordering::SeqCst);
+        }
+        local.pins.set(pins + 1);
+    });
+    Guard { _not_send: PhantomData }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        LOCAL.with(|local| {
+            let pins = local.pins.get() - 1;
+            local.pins.set(pins);
+            if pins == 0 {
+                local.participant.state.store(0, Ordering::SeqCst);
+            }
+        });
+    }
+}
+
+impl Guard {
+    // 延迟释放一个由 Box::into_raw 得到的指针
+    //
+    // Safety: ptr 必须来自 Box::<T>::into_raw，已经从共享结构中摘除（新 pin 的线程不可能再读到它），
+    // 并且只 retire 一次
+    pub unsafe fn retire<T: Send>(&self, ptr: *mut T) {
+        fence(Ordering::SeqCst);
+        let retired = Retired { ptr: ptr as *mut u8, drop_fn: drop_box::<T>, epoch: EPOCH.load(Ordering::SeqCst) };
+        let full = LOCAL.with(|local| {
+            local.bag.borrow_mut().push(retired);
+            // 按新增的个数而不是袋子的大小触发，纪元一时推进不了时不会每次 retire 都去回收
+            let count = local.retired_since_collect.get() + 1;
+            local.retired_since_collect.set(count % COLLECT_THRESHOLD);
+            count == COLLECT_THRESHOLD
+        });
+        if full {
+            collect();
+        }
+    }
+}
+
+// 所有正在 pin 的线程都处在当前纪元时，把全局纪元加一。返回之后的全局纪元
+fn try_advance() -> usize {
+    let global = EPOCH.load(Ordering::SeqCst);
+    fence(Ordering::SeqCst);
+    for participant in PARTICIPANTS.lock().unwrap().iter() {
+        let state = participant.state.load(Ordering::SeqCst);
+        if state & 1 == 1 && state >> 1 != global {
+            return global;
+        }
+    }
+    match EPOCH.compare_exchange(global, global + 1, Ordering::SeqCst, Ordering::SeqCst) {
+        Ok(_) => global + 1,
+        Err(now) => now,
+    }
+}
+
+// garbage 按纪元排好序，可以释放的都在前面
+fn free_expired(garbage: &mut Vec<Retired>, global: usize) -> usize {
+    let expired = garbage.partition_point(|r| r.epoch + 2 <= global);
+    for r in garbage.drain(..expired) {
+        // SAFETY: 见模块开头的第 4 条
+        unsafe { (r.drop_fn)(r.ptr) };
+    }
+    expired
+}
+
+// 尝试推进纪元，并释放本线程和孤儿列表中已经安全的节点，返回释放的个数
+pub fn collect() -> usize {
+    let global = try_advance();
+    // 先把袋子取出来再释放，节点的析构函数里也可能 retire
+    let mut bag = LOCAL.with(|local| std::mem::take(&mut *local.bag.borrow_mut()));
+    let mut freed = free_expired(&mut bag, global);
+    LOCAL.with(|local| {
+        // 析构期间新 retire 的节点纪元更新，排在后面
+        let mut current = local.bag.borrow_mut();
+        bag.append(&mut current);
+        *current = bag;
+    });
+    let mut orphans = std::mem::take(&mut *ORPHANS.lock().unwrap());
+    if !orphans.is_empty() {
+        freed += free_expired(&mut orphans, global);
+        let mut shared = ORPHANS.lock().unwrap();
+        shared.append(&mut orphans);
+        shared.sort_by_key(|r| r.epoch);
+    }
+    freed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicBool;
+    use std::thread;
+
+    struct Tracked(Arc<AtomicBool>);
+
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn retired_objects_wait_for_pinned_threads() {
+        let dropped = Arc::new(AtomicBool::new(false));
+        let ptr = Box::into_raw(Box::new(Tracked(Arc::clone(&dropped))));
+
+        // 另一个线程一直 pin 着，纪元无法前进两格，对象不能被释放
+        let (pinned_tx, pinned_rx) = std::sync::mpsc::channel();
+        let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+        let reader = thread::spawn(move || {
+            let _guard = pin();
+            pinned_tx.send(()).unwrap();
+            let _ = release_rx.recv();
+        });
+        pinned_rx.recv().unwrap();
+
+        unsafe { pin().retire(ptr) };
+        for _ in 0..10 {
+            collect();
+        }
+        assert!(!dropped.load(Ordering::SeqCst));
+
+        release_tx.send(()).unwrap();
+        reader.join().unwrap();
+        for _ in 0..10 {
+            collect();
+        }
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+}
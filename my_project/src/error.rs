@@ -0,0 +1,298 @@
+#![allow(dead_code)]
+
+// 统一的错误类型
+// exceptions.rs 讲到 `type AliasedResult<T> = Result<T, ParseIntError>` 就结束了：一个函数只能返回一种错误。
+// 实际代码里读文件会遇到 io::Error，解析数字会遇到 ParseIntError / ParseFloatError，
+// 读取子进程的输出会遇到 FromUtf8Error。这里定义一个 crate 内通用的 Error：
+// - 为这些标准库错误实现 From，`?` 会自动转换，不同来源的错误可以在同一个函数里用 `?` 传播
+// - Result 和 Option 都有 .context("...") 扩展，在错误外面再包一层说明“当时在做什么”，原来的错误成为 source()
+// - 创建错误时捕获调用栈（Backtrace::capture，设置 RUST_BACKTRACE=1 时才真正捕获）
+// - report() 沿着 source() 链打印完整的错误原因，例如：
+//     error: while reading config
+//     caused by:
+//       0: invalid integer
+//       1: invalid digit found in string
+//   这几种标准库错误的说明文字来自消息目录（i18n.rs），原始错误的信息由标准库生成，不翻译
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::error::Error as StdError;
+use std::fmt::{self, Write};
+use std::io;
+use std::num::{ParseFloatError, ParseIntError};
+use std::str::Utf8Error;
+use std::string::FromUtf8Error;
+
+use crate::i18n::tr;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    Io(io::Error),
+    ParseInt(ParseIntError),
+    ParseFloat(ParseFloatError),
+    Utf8(Utf8Error),
+    Custom(String),
+    Other(Box<dyn StdError + Send + Sync>), // 其他模块的错误类型，例如 SupervisorError
+    Context { message: String, source: Box<Error> },
+}
+
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    backtrace: Backtrace,
+}
+
+impl Error {
+    fn new(kind: ErrorKind) -> Error {
+        Error { kind, backtrace: Backtrace::capture() }
+    }
+
+    pub fn msg(message: impl Into<String>) -> Error {
+        Error::new(ErrorKind::Custom(message.into()))
+    }
+
+    pub fn other(error: impl StdError + Send + Sync + 'static) -> Error {
+        Error::new(ErrorKind::Other(Box::new(error)))
+    }
+
+    // 包一层说明，原来的错误成为 source()。调用栈沿用最初的那一个，不重新捕获
+    pub fn context(self, message: impl Into<String>) -> Error {
+        Error {
+            kind: ErrorKind::Context { message: message.into(), source: Box::new(self) },
+            backtrace: Backtrace::disabled(),
+        }
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    // 去掉所有 context 之后最初的错误
+    pub fn root(&self) -> &Error {
+        match &self.kind {
+            ErrorKind::Context { source, .. } => source.root(),
+            _ => self,
+        }
+    }
+
+    pub fn backtrace(&self) -> &Backtrace {
+        &self.root().backtrace
+    }
+
+    // 完整的错误报告：错误本身、每一层原因，以及捕获到的调用栈
+    pub fn report(&self) -> String {
+        let mut out = report(self);
+        if self.backtrace().status() == BacktraceStatus::Captured {
+            let _ = write!(out, "\n\nstack backtrace:\n{}", self.backtrace());
+        }
+        out
+    }
+}
+
+// 适用于任何实现了 std::error::Error 的错误，沿着 source() 链逐层打印
+pub fn report(error: &(dyn StdError + 'static)) -> String {
+    let mut out = format!("error: {}", error);
+    let mut source = error.source();
+    if source.is_some() {
+        out.push_str("\ncaused by:");
+    }
+    let mut depth = 0;
+    while let Some(cause) = source {
+        let _ = write!(out, "\n  {}: {}", depth, cause);
+        source = cause.source();
+        depth += 1;
+    }
+    out
+}
+
+impl fmt::Display for Error {
+    // 只描述这一层，下一层的细节由 source() 提供，report 时不会重复
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            ErrorKind::Io(_) => f.write_str(&tr!("error.io")),
+            ErrorKind::ParseInt(_) => f.write_str(&tr!("error.parse_int")),
+            ErrorKind::ParseFloat(_) => f.write_str(&tr!("error.parse_float")),
+            ErrorKind::Utf8(_) => f.write_str(&tr!("error.utf8")),
+            ErrorKind::Custom(message) => write!(f, "{}", message),
+            ErrorKind::Other(error) => write!(f, "{}", error),
+            ErrorKind::Context { message, .. } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match &self.kind {
+            ErrorKind::Io(e) => Some(e),
+            ErrorKind::ParseInt(e) => Some(e),
+            ErrorKind::ParseFloat(e) => Some(e),
+            ErrorKind::Utf8(e) => Some(e),
+            ErrorKind::Custom(_) => None,
+            ErrorKind::Other(e) => e.source(),
+            ErrorKind::Context { source, .. } => Some(&**source),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::new(ErrorKind::Io(e))
+    }
+}
+
+impl From<ParseIntError> for Error {
+    fn from(e: ParseIntError) -> Self {
+        Error::new(ErrorKind::ParseInt(e))
+    }
+}
+
+impl From<ParseFloatError> for Error {
+    fn from(e: ParseFloatError) -> Self {
+        Error::new(ErrorKind::ParseFloat(e))
+    }
+}
+
+impl From<Utf8Error> for Error {
+    fn from(e: Utf8Error) -> Self {
+        Error::new(ErrorKind::Utf8(e))
+    }
+}
+
+impl From<FromUtf8Error> for Error {
+    fn from(e: FromUtf8Error) -> Self {
+        Error::new(ErrorKind::Utf8(e.utf8_error()))
+    }
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error::msg(message)
+    }
+}
+
+impl From<&str> for Error {
+    fn from(message: &str) -> Self {
+        Error::msg(message)
+    }
+}
+
+// .context() 扩展。Result 的错误可以是任何能转换成 Error 的类型；Option 的 None 直接变成以 message 为内容的错误
+pub trait Context<T> {
+    fn context(self, message: impl Into<String>) -> Result<T>;
+
+    // 说明需要拼接字符串时用这个，只有出错时才会调用 f
+    fn with_context<S: Into<String>>(self, f: impl FnOnce() -> S) -> Result<T>;
+}
+
+impl<T, E: Into<Error>> Context<T> for std::result::Result<T, E> {
+    fn context(self, message: impl Into<String>) -> Result<T> {
+        self.map_err(|e| e.into().context(message))
+    }
+
+    fn with_context<S: Into<String>>(self, f: impl FnOnce() -> S) -> Result<T> {
+        self.map_err(|e| e.into().context(f()))
+    }
+}
+
+impl<T> Context<T> for Option<T> {
+    fn context(self, message: impl Into<String>) -> Result<T> {
+        self.ok_or_else(|| Error::msg(message))
+    }
+
+    fn with_context<S: Into<String>>(self, f: impl FnOnce() -> S) -> Result<T> {
+        self.ok_or_else(|| Error::msg(f()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    // 读取 "key = value" 格式的配置，同一个函数里混合了 io、解析和 Option 三种错误
+    fn read_config(path: &str) -> Result<(u16, f64)> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("while reading config {}", path))?;
+        let value = |key: &str| -> Result<&str> {
+            text.lines()
+                .filter_map(|line| line.split_once('='))
+                .find(|(k, _)| k.trim() == key)
+                .map(|(_, v)| v.trim())
+                .with_context(|| format!("missing key '{}'", key))
+        };
+        let port = value("port")?.parse::<u16>().context("while parsing port")?;
+        let ratio = value("ratio")?.parse::<f64>()?;
+        Ok((port, ratio))
+    }
+
+    // 每个测试一个临时目录，测试结束（包括断言失败）时连同里面的配置文件一起删除
+    struct TempDir(std::path::PathBuf);
+
+    impl TempDir {
+        fn new(test: &str) -> TempDir {
+            let dir = std::env::temp_dir().join(format!("my_project_error_{}_{}", std::process::id(), test));
+            std::fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+
+        fn write_config(&self, name: &str, text: &str) -> String {
+            let path = self.0.join(name);
+            std::fs::write(&path, text).unwrap();
+            path.to_string_lossy().into_owned()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn question_mark_converts_every_kind() {
+        let dir = TempDir::new("question_mark");
+        let good = dir.write_config("good", "port = 8080\nratio = 0.5\n");
+        assert_eq!(read_config(&good).unwrap(), (8080, 0.5));
+
+        let missing = read_config("/definitely/not/here.conf").unwrap_err();
+        assert!(matches!(missing.root().kind(), ErrorKind::Io(e) if e.kind() == io::ErrorKind::NotFound));
+
+        let bad_port = read_config(&dir.write_config("port", "port = eighty\nratio = 1\n")).unwrap_err();
+        assert!(matches!(bad_port.root().kind(), ErrorKind::ParseInt(_)));
+
+        let bad_ratio = read_config(&dir.write_config("ratio", "port = 80\nratio = half\n")).unwrap_err();
+        assert!(matches!(bad_ratio.kind(), ErrorKind::ParseFloat(_)));
+
+        let no_key = read_config(&dir.write_config("key", "port = 80\n")).unwrap_err();
+        assert_eq!(no_key.to_string(), "missing key 'ratio'");
+    }
+
+    #[test]
+    fn utf8_errors_from_process_output() {
+        fn run() -> Result<String> {
+            let output = Command::new("printf").arg("\\377\\376").output()?;
+            Ok(String::from_utf8(output.stdout)?)
+        }
+        assert!(matches!(run().unwrap_err().kind(), ErrorKind::Utf8(_)));
+    }
+
+    #[test]
+    fn report_walks_the_source_chain() {
+        let dir = TempDir::new("report");
+        let path = dir.write_config("report", "port = 99999\n");
+        let error = read_config(&path).context("while starting the server").unwrap_err();
+        let report = error.report();
+        let lines: Vec<&str> = report.lines().take(5).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "error: while starting the server",
+                "caused by:",
+                "  0: while parsing port",
+                format!("  1: {}", tr!("error.parse_int")).as_str(),
+                "  2: number too large to fit in target type",
+            ]
+        );
+        let other = Error::other(crate::actor::ActorStopped).context("while sending");
+        assert_eq!(super::report(&other), "error: while sending\ncaused by:\n  0: actor has stopped");
+    }
+}
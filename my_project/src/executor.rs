@@ -0,0 +1,656 @@
+#![allow(dead_code)]
+
+// 只依赖标准库的异步运行时
+// Future 是一个可以被反复 poll 的状态机：poll 返回 Poll::Ready(值) 表示完成，
+// 返回 Poll::Pending 表示还没准备好，并且它保证在“可以继续”的时候调用 cx.waker().wake()。
+// 执行器（executor）的工作就是：把任务放进队列，取出来 poll，Pending 的任务等它被 wake 后再放回队列。
+// 本模块包括：
+// 1. block_on：在当前线程上运行一个 Future，Pending 时 park 线程，wake 时 unpark
+// 2. Executor：单线程执行器，spawn 的任务放进工作队列，run 执行到所有任务结束
+// 3. Runtime：多线程执行器，固定数量的 worker 线程共享同一个工作队列
+// 4. sleep：由一个后台计时线程在到期时唤醒的计时器 Future
+// 5. channel：接收端是异步的无界通道，recv().await 在没有消息时让出执行权
+// 任务 panic 不会杀死 worker 线程：panic 被捕获并交给 JoinHandle，await 这个句柄的地方会重新抛出它，
+// 和 thread::JoinHandle::join 之后 resume_unwind 的效果一样。
+use std::any::Any;
+use std::cmp::Ordering as CmpOrdering;
+use std::collections::{BinaryHeap, VecDeque};
+use std::future::Future;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, JoinHandle as ThreadHandle, Thread};
+use std::time::{Duration, Instant};
+
+type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+type Payload = Box<dyn Any + Send + 'static>;
+
+// block_on 的唤醒器：wake 就是 unpark 被阻塞的线程
+struct ThreadWaker {
+    thread: Thread,
+    woken: AtomicBool,
+}
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.store(true, Ordering::SeqCst);
+        self.thread.unpark();
+    }
+}
+
+// 在当前线程上运行 future 直到完成
+pub fn block_on<F: Future>(fut: F) -> F::Output {
+    let mut fut = Box::pin(fut);
+    let signal = Arc::new(ThreadWaker { thread: thread::current(), woken: AtomicBool::new(false) });
+    let waker = Waker::from(Arc::clone(&signal));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(out) = fut.as_mut().poll(&mut cx) {
+            return out;
+        }
+        // park 可能被虚假唤醒，所以用 woken 标记确认真的被 wake 过
+        while !signal.woken.swap(false, Ordering::SeqCst) {
+            thread::park();
+        }
+    }
+}
+
+// 就绪任务的队列，单线程和多线程执行器共用
+struct Queue {
+    tasks: Mutex<VecDeque<Arc<Task>>>,
+    ready: Condvar,
+    live: AtomicUsize, // 尚未完成的任务数
+    shutdown: AtomicBool,
+}
+
+impl Queue {
+    fn new() -> Arc<Queue> {
+        Arc::new(Queue {
+            tasks: Mutex::new(VecDeque::new()),
+            ready: Condvar::new(),
+            live: AtomicUsize::new(0),
+            shutdown: AtomicBool::new(false),
+        })
+    }
+
+    fn push(&self, task: Arc<Task>) {
+        self.tasks.lock().unwrap().push_back(task);
+        self.ready.notify_one();
+    }
+
+    // 取出下一个就绪任务；stop 返回 true 时不再等待，返回 None
+    fn pop(&self, stop: impl Fn(&Queue) -> bool) -> Option<Arc<Task>> {
+        let mut tasks = self.tasks.lock().unwrap();
+        loop {
+            if let Some(task) = tasks.pop_front() {
+                return Some(task);
+            }
+            if stop(self) {
+                return None;
+            }
+            tasks = self.ready.wait(tasks).unwrap();
+        }
+    }
+
+    fn task_finished(&self) {
+        if self.live.fetch_sub(1, Ordering::SeqCst) == 1 {
+            // 最后一个任务结束，唤醒在 run 中等待的线程
+            let _guard = self.tasks.lock().unwrap();
+            self.ready.notify_all();
+        }
+    }
+}
+
+struct Task {
+    future: Mutex<Option<BoxFuture>>, // 完成后置为 None
+    queue: Arc<Queue>,
+    scheduled: AtomicBool, // 已经在队列中，避免重复入队
+}
+
+impl Wake for Task {
+    fn wake(self: Arc<Self>) {
+        if !self.scheduled.swap(true, Ordering::SeqCst) {
+            let queue = Arc::clone(&self.queue);
+            queue.push(self);
+        }
+    }
+}
+
+impl Task {
+    fn run(self: Arc<Self>) {
+        // 先清除标记，poll 期间的 wake 会让任务重新入队
+        self.scheduled.store(false, Ordering::SeqCst);
+        let mut slot = self.future.lock().unwrap();
+        let fut = match slot.as_mut() {
+            Some(fut) => fut,
+            None => return,
+        };
+        let waker = Waker::from(Arc::clone(&self));
+        // spawn 已经用 CatchUnwind 包装过任务，这里是最后一道防线：无论如何不能让 worker 线程跟着退出，
+        // 也不能在持有 slot 时 unwind（会毒化 future 的锁）
+        let polled = panic::catch_unwind(AssertUnwindSafe(|| fut.as_mut().poll(&mut Context::from_waker(&waker))));
+        if !matches!(polled, Ok(Poll::Pending)) {
+            *slot = None;
+            self.queue.task_finished();
+        }
+    }
+}
+
+// 在 poll 时捕获 panic，把它变成 Err(payload)
+struct CatchUnwind<F>(Pin<Box<F>>);
+
+impl<F: Future> Future for CatchUnwind<F> {
+    type Output = Result<F::Output, Payload>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // JoinHandle 用 resume_unwind 重新抛出，不会再经过 panic 钩子，所以这里不标记为已捕获（pool::run_catching）
+        match panic::catch_unwind(AssertUnwindSafe(|| self.0.as_mut().poll(cx))) {
+            Ok(Poll::Pending) => Poll::Pending,
+            Ok(Poll::Ready(value)) => Poll::Ready(Ok(value)),
+            Err(payload) => Poll::Ready(Err(payload)),
+        }
+    }
+}
+
+// spawn 返回的句柄，本身也是一个 Future，输出任务的返回值。任务 panic 时，await 句柄会重新抛出同一个 panic
+pub struct JoinHandle<T> {
+    state: Arc<Mutex<JoinState<T>>>,
+}
+
+struct JoinState<T> {
+    result: Option<Result<T, Payload>>,
+    waker: Option<Waker>,
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let result = {
+            let mut state = self.state.lock().unwrap();
+            let result = state.result.take();
+            if result.is_none() {
+                state.waker = Some(cx.waker().clone());
+            }
+            result
+        };
+        // 在锁外重新抛出，不毒化 JoinState
+        match result {
+            Some(Ok(value)) => Poll::Ready(value),
+            Some(Err(payload)) => panic::resume_unwind(payload),
+            None => Poll::Pending,
+        }
+    }
+}
+
+// 可以 clone 的任务提交入口，任务内部也可以用它继续 spawn
+#[derive(Clone)]
+pub struct Spawner {
+    queue: Arc<Queue>,
+}
+
+impl Spawner {
+    pub fn spawn<F>(&self, fut: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let state = Arc::new(Mutex::new(JoinState { result: None, waker: None }));
+        let join_state = Arc::clone(&state);
+        let wrapped = async move {
+            let value = CatchUnwind(Box::pin(fut)).await;
+            let mut state = join_state.lock().unwrap();
+            state.result = Some(value);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        };
+        self.queue.live.fetch_add(1, Ordering::SeqCst);
+        let task = Arc::new(Task {
+            future: Mutex::new(Some(Box::pin(wrapped))),
+            queue: Arc::clone(&self.queue),
+            scheduled: AtomicBool::new(true),
+        });
+        self.queue.push(task);
+        JoinHandle { state }
+    }
+}
+
+// 单线程执行器：所有任务都在调用 run 的线程上执行
+pub struct Executor {
+    queue: Arc<Queue>,
+}
+
+impl Default for Executor {
+    fn default() -> Self {
+        Executor::new()
+    }
+}
+
+impl Executor {
+    pub fn new() -> Executor {
+        Executor { queue: Queue::new() }
+    }
+
+    pub fn spawner(&self) -> Spawner {
+        Spawner { queue: Arc::clone(&self.queue) }
+    }
+
+    pub fn spawn<F>(&self, fut: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.spawner().spawn(fut)
+    }
+
+    // 执行任务直到全部完成
+    pub fn run(&self) {
+        while let Some(task) = self.queue.pop(|q| q.live.load(Ordering::SeqCst) == 0) {
+            task.run();
+        }
+    }
+}
+
+// 多线程执行器：workers 个线程从共享队列中取任务执行
+pub struct Runtime {
+    queue: Arc<Queue>,
+    workers: Vec<ThreadHandle<()>>,
+}
+
+impl Runtime {
+    pub fn new(workers: usize) -> Runtime {
+        assert!(workers > 0, "runtime needs at least one worker");
+        let queue = Queue::new();
+        let workers = (0..workers)
+            .map(|i| {
+                let queue = Arc::clone(&queue);
+                thread::Builder::new()
+                    .name(format!("async-worker-{}", i))
+                    .spawn(move || {
+                        while let Some(task) = queue.pop(|q| q.shutdown.load(Ordering::SeqCst)) {
+                            if queue.shutdown.load(Ordering::SeqCst) {
+                                break;
+                            }
+                            task.run();
+                        }
+                    })
+                    .expect("failed to spawn runtime worker")
+            })
+            .collect();
+        Runtime { queue, workers }
+    }
+
+    pub fn spawner(&self) -> Spawner {
+        Spawner { queue: Arc::clone(&self.queue) }
+    }
+
+    pub fn spawn<F>(&self, fut: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.spawner().spawn(fut)
+    }
+
+    // 把 fut 交给 worker 执行，当前线程阻塞等待结果。fut panic 时在当前线程重新抛出
+    pub fn block_on<F>(&self, fut: F) -> F::Output
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        block_on(self.spawn(fut))
+    }
+}
+
+impl Drop for Runtime {
+    // 停止 worker；队列中剩余的任务不再执行。
+    // 任务要在锁外 drop：future 里可能持有 Sender 之类的东西，drop 时会唤醒其他任务，Task::wake 要再次锁住队列
+    fn drop(&mut self) {
+        self.queue.shutdown.store(true, Ordering::SeqCst);
+        let pending = {
+            let mut tasks = self.queue.tasks.lock().unwrap();
+            self.queue.ready.notify_all();
+            mem::take(&mut *tasks)
+        };
+        drop(pending);
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+        // 上面 drop 任务时被唤醒的任务又回到了队列里，同样在锁外丢掉
+        loop {
+            let woken = mem::take(&mut *self.queue.tasks.lock().unwrap());
+            if woken.is_empty() {
+                break;
+            }
+            drop(woken);
+        }
+    }
+}
+
+// 计时器：一个后台线程按到期时间顺序（小顶堆）唤醒等待者。
+// 每个 Sleep 只登记一次，之后的 poll 只更新共享的 waker（任务可能换了 waker）
+type SharedWaker = Arc<Mutex<Waker>>;
+
+struct TimerEntry {
+    deadline: Instant,
+    waker: SharedWaker,
+}
+
+impl PartialEq for TimerEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.deadline == other.deadline
+    }
+}
+
+impl Eq for TimerEntry {}
+
+impl PartialOrd for TimerEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TimerEntry {
+    // BinaryHeap 是大顶堆，反转比较使最早到期的排在堆顶
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        other.deadline.cmp(&self.deadline)
+    }
+}
+
+struct Timers {
+    heap: Mutex<BinaryHeap<TimerEntry>>,
+    changed: Condvar,
+}
+
+fn timers() -> &'static Timers {
+    static TIMERS: OnceLock<&'static Timers> = OnceLock::new();
+    TIMERS.get_or_init(|| {
+        let timers: &'static Timers = Box::leak(Box::new(Timers {
+            heap: Mutex::new(BinaryHeap::new()),
+            changed: Condvar::new(),
+        }));
+        thread::Builder::new()
+            .name("async-timer".to_string())
+            .spawn(move || timer_loop(timers))
+            .expect("failed to spawn timer thread");
+        timers
+    })
+}
+
+fn timer_loop(timers: &Timers) {
+    let mut heap = timers.heap.lock().unwrap();
+    loop {
+        let now = Instant::now();
+        while heap.peek().is_some_and(|e| e.deadline <= now) {
+            heap.pop().unwrap().waker.lock().unwrap().wake_by_ref();
+        }
+        heap = match heap.peek() {
+            Some(next) => {
+                let timeout = next.deadline - now;
+                timers.changed.wait_timeout(heap, timeout).unwrap().0
+            }
+            None => timers.changed.wait(heap).unwrap(),
+        };
+    }
+}
+
+// 异步睡眠：不占用线程，到期时由计时线程唤醒
+pub struct Sleep {
+    deadline: Instant,
+    waker: Option<SharedWaker>, // 已经登记到计时线程
+}
+
+pub fn sleep(duration: Duration) -> Sleep {
+    Sleep { deadline: Instant::now() + duration, waker: None }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if Instant::now() >= self.deadline {
+            return Poll::Ready(());
+        }
+        match &self.waker {
+            Some(shared) => {
+                let mut waker = shared.lock().unwrap();
+                if !waker.will_wake(cx.waker()) {
+                    *waker = cx.waker().clone();
+                }
+            }
+            None => {
+                let shared = Arc::new(Mutex::new(cx.waker().clone()));
+                let timers = timers();
+                timers.heap.lock().unwrap().push(TimerEntry { deadline: self.deadline, waker: Arc::clone(&shared) });
+                timers.changed.notify_one();
+                self.waker = Some(shared);
+            }
+        }
+        Poll::Pending
+    }
+}
+
+// 异步通道：发送不阻塞，接收端 recv().await 在队列为空时挂起
+struct ChannelState<T> {
+    queue: VecDeque<T>,
+    waker: Option<Waker>,
+    senders: usize,
+    receiver_alive: bool,
+}
+
+pub struct Sender<T> {
+    state: Arc<Mutex<ChannelState<T>>>,
+}
+
+pub struct Receiver<T> {
+    state: Arc<Mutex<ChannelState<T>>>,
+}
+
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let state = Arc::new(Mutex::new(ChannelState {
+        queue: VecDeque::new(),
+        waker: None,
+        senders: 1,
+        receiver_alive: true,
+    }));
+    (Sender { state: Arc::clone(&state) }, Receiver { state })
+}
+
+impl<T> Sender<T> {
+    // 接收端已 drop 时把消息交还给调用者
+    pub fn send(&self, value: T) -> Result<(), T> {
+        let mut state = self.state.lock().unwrap();
+        if !state.receiver_alive {
+            return Err(value);
+        }
+        state.queue.push_back(value);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        Ok(())
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.state.lock().unwrap().senders += 1;
+        Sender { state: Arc::clone(&self.state) }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.state.lock().unwrap();
+        state.senders -= 1;
+        if state.senders == 0 {
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    // 返回 None 表示所有发送端都已 drop 且没有剩余消息
+    pub fn recv(&mut self) -> Recv<'_, T> {
+        Recv { receiver: self }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.state.lock().unwrap();
+        state.receiver_alive = false;
+        state.queue.clear();
+    }
+}
+
+pub struct Recv<'a, T> {
+    receiver: &'a mut Receiver<T>,
+}
+
+impl<T> Future for Recv<'_, T> {
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let mut state = self.receiver.state.lock().unwrap();
+        if let Some(value) = state.queue.pop_front() {
+            return Poll::Ready(Some(value));
+        }
+        if state.senders == 0 {
+            return Poll::Ready(None);
+        }
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicU64;
+
+    #[test]
+    fn block_on_runs_async_code() {
+        let started = Instant::now();
+        let out = block_on(async {
+            sleep(Duration::from_millis(30)).await;
+            21 * 2
+        });
+        assert_eq!(out, 42);
+        assert!(started.elapsed() >= Duration::from_millis(30));
+    }
+
+    #[test]
+    fn executor_runs_spawned_tasks_and_channels() {
+        let executor = Executor::new();
+        let spawner = executor.spawner();
+        let (tx, mut rx) = channel::<u32>();
+        let consumer = executor.spawn(async move {
+            let mut sum = 0;
+            while let Some(v) = rx.recv().await {
+                sum += v;
+            }
+            sum
+        });
+        for i in 0..10 {
+            let tx = tx.clone();
+            spawner.spawn(async move {
+                sleep(Duration::from_millis(10 - i as u64)).await;
+                tx.send(i).unwrap();
+            });
+        }
+        drop(tx);
+        let total = Arc::new(AtomicU64::new(0));
+        let result = Arc::clone(&total);
+        executor.spawn(async move {
+            result.store(consumer.await as u64, Ordering::SeqCst);
+        });
+        executor.run();
+        assert_eq!(total.load(Ordering::SeqCst), 45);
+    }
+
+    #[test]
+    fn multi_threaded_runtime() {
+        let runtime = Runtime::new(4);
+        let spawner = runtime.spawner();
+        let sum = runtime.block_on(async move {
+            let handles: Vec<_> = (0..100u64)
+                .map(|i| {
+                    spawner.spawn(async move {
+                        if i % 10 == 0 {
+                            sleep(Duration::from_millis(5)).await;
+                        }
+                        i * i
+                    })
+                })
+                .collect();
+            let mut sum = 0;
+            for h in handles {
+                sum += h.await;
+            }
+            sum
+        });
+        assert_eq!(sum, (0..100u64).map(|i| i * i).sum());
+    }
+
+    #[test]
+    fn panicking_task_does_not_kill_the_worker() {
+        let runtime = Runtime::new(1);
+        let bad = runtime.spawn(async {
+            sleep(Duration::from_millis(5)).await;
+            panic!("task failed");
+        });
+        let good = runtime.spawn(async {
+            sleep(Duration::from_millis(20)).await;
+            7
+        });
+        let payload = panic::catch_unwind(AssertUnwindSafe(|| runtime.block_on(bad))).unwrap_err();
+        assert_eq!(crate::pool::panic_message(&*payload), "task failed");
+        // 唯一的 worker 仍然活着，后面的任务照常完成
+        assert_eq!(runtime.block_on(good), 7);
+        assert_eq!(runtime.block_on(async { 1 + 1 }), 2);
+    }
+
+    #[test]
+    fn dropping_a_queued_task_may_wake_another() {
+        let (done_tx, done_rx) = std::sync::mpsc::channel();
+        thread::spawn(move || {
+            let runtime = Runtime::new(1);
+            let (tx, mut rx) = channel::<i32>();
+            let (busy_tx, busy_rx) = std::sync::mpsc::channel();
+            // 先运行，在 recv 上等待
+            let _receiver = runtime.spawn(async move { rx.recv().await });
+            // 占住唯一的 worker，下面持有 Sender 的任务只能留在队列里
+            runtime.spawn(async move {
+                busy_tx.send(()).unwrap();
+                thread::sleep(Duration::from_millis(50));
+            });
+            let _sender = runtime.spawn(async move { tx.send(1).is_ok() });
+            busy_rx.recv().unwrap();
+            drop(runtime);
+            done_tx.send(()).unwrap();
+        });
+        done_rx.recv_timeout(Duration::from_secs(5)).expect("dropping the runtime deadlocked");
+    }
+
+    #[test]
+    fn sleep_registers_its_timer_once() {
+        let deadline = Instant::now() + Duration::from_secs(60);
+        let mut sleep = Box::pin(Sleep { deadline, waker: None });
+        let waker = Waker::from(Arc::new(ThreadWaker { thread: thread::current(), woken: AtomicBool::new(false) }));
+        for _ in 0..10 {
+            assert!(sleep.as_mut().poll(&mut Context::from_waker(&waker)).is_pending());
+        }
+        let registered = timers().heap.lock().unwrap().iter().filter(|e| e.deadline == deadline).count();
+        assert_eq!(registered, 1);
+    }
+}
@@ -0,0 +1,450 @@
+#![allow(dead_code)]
+
+// 算术表达式求值
+// exceptions.rs 的 multiply_2 只能把两个整数相乘，macros.rs 的 calculate! 只能在编译期计算。
+// 这里在运行时解析并计算用户写在配置文件里的公式，例如 `(1 + 2) * 3 / 4 - -5 ^ 2`：
+// 1. 词法分析（lexer）：把字符串切成记号（token），每个记号记住自己在原文中的字节范围（span）
+// 2. 语法分析（parser）：递归下降，按优先级从低到高：
+//      expr  := term (('+' | '-') term)*
+//      term  := unary (('*' | '/' | '%') unary)*
+//      unary := '-' unary | power
+//      power := atom ('^' unary)?          乘方右结合，并且比一元负号优先：-5 ^ 2 = -(5 ^ 2)
+//      atom  := 数字 | 变量名 | '(' expr ')'
+// 3. 求值：整数模式用 i64 的 checked_* 运算，溢出报错而不是回绕；浮点模式用 f64，结果不是有限数时报错
+// 出错时 ExprError 带着出错位置，diagnostic 在原文下面用 ^ 标出来。
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+use crate::i18n::{messages, Args, Messages};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    fn to(self, other: Span) -> Span {
+        Span { start: self.start, end: other.end }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprErrorKind {
+    UnexpectedChar(char),
+    UnexpectedToken(String),
+    UnexpectedEnd,
+    UnclosedParen,
+    UnknownVariable(String),
+    DivisionByZero,
+    Overflow,
+    NegativeExponent,
+    FloatInIntegerMode,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExprError {
+    pub kind: ExprErrorKind,
+    pub span: Span,
+}
+
+impl fmt::Display for ExprError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message_in(messages()))
+    }
+}
+
+impl Error for ExprError {}
+
+impl ExprError {
+    fn new(kind: ExprErrorKind, span: Span) -> ExprError {
+        ExprError { kind, span }
+    }
+
+    // 用指定的消息目录生成说明文字；Display 用的是全局的 messages()
+    pub fn message_in(&self, messages: &Messages) -> String {
+        let message = match &self.kind {
+            ExprErrorKind::UnexpectedChar(c) => messages.get("expr.unexpected_char", &[("char", c)]),
+            ExprErrorKind::UnexpectedToken(t) => messages.get("expr.unexpected_token", &[("token", t)]),
+            ExprErrorKind::UnexpectedEnd => messages.get("expr.unexpected_end", &[]),
+            ExprErrorKind::UnclosedParen => messages.get("expr.unclosed_paren", &[]),
+            ExprErrorKind::UnknownVariable(name) => messages.get("expr.unknown_variable", &[("name", name)]),
+            ExprErrorKind::DivisionByZero => messages.get("expr.division_by_zero", &[]),
+            ExprErrorKind::Overflow => messages.get("expr.overflow", &[]),
+            ExprErrorKind::NegativeExponent => messages.get("expr.negative_exponent", &[]),
+            ExprErrorKind::FloatInIntegerMode => messages.get("expr.float_in_integer_mode", &[]),
+        };
+        let args: &Args = &[("message", &message), ("start", &self.span.start), ("end", &self.span.end)];
+        messages.get("expr.at", args)
+    }
+
+    // 在原文下面标出出错的位置：
+    //   error: division by zero at 8..15
+    //     | 1 + 2 / (3 - 3)
+    //     |         ^^^^^^^
+    pub fn diagnostic(&self, src: &str) -> String {
+        self.diagnostic_in(src, messages())
+    }
+
+    pub fn diagnostic_in(&self, src: &str, messages: &Messages) -> String {
+        // span 是字节位置，^ 要按字符数对齐
+        let pad = src[..self.span.start].chars().count();
+        let width = src[self.span.start..self.span.end].chars().count().max(1);
+        let error = messages.get("expr.diagnostic", &[("message", &self.message_in(messages))]);
+        format!("{}\n  | {}\n  | {}{}", error, src, " ".repeat(pad), "^".repeat(width))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(String),
+    Ident(String),
+    Op(char), // + - * / % ^ ( )
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Number(s) | Token::Ident(s) => write!(f, "{}", s),
+            Token::Op(c) => write!(f, "{}", c),
+        }
+    }
+}
+
+fn tokenize(src: &str) -> Result<Vec<(Token, Span)>, ExprError> {
+    let mut tokens = vec![];
+    let mut chars = src.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        let mut take_while = |pred: fn(char) -> bool| {
+            let mut end = start;
+            while let Some(&(i, c)) = chars.peek() {
+                if !pred(c) {
+                    break;
+                }
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            (src[start..end].to_string(), Span { start, end })
+        };
+        if c.is_whitespace() {
+            take_while(char::is_whitespace);
+        } else if c.is_ascii_digit() || c == '.' {
+            let (text, span) = take_while(|c| c.is_ascii_digit() || c == '.');
+            tokens.push((Token::Number(text), span));
+        } else if c.is_alphabetic() || c == '_' {
+            let (text, span) = take_while(|c| c.is_alphanumeric() || c == '_');
+            tokens.push((Token::Ident(text), span));
+        } else if "+-*/%^()".contains(c) {
+            chars.next();
+            tokens.push((Token::Op(c), Span { start, end: start + 1 }));
+        } else {
+            return Err(ExprError::new(ExprErrorKind::UnexpectedChar(c), Span { start, end: start + c.len_utf8() }));
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    Number(String), // 数字的原文，求值时按模式解析
+    Var(String),
+    Neg(Box<Expr>),
+    Binary(char, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+struct Parser {
+    tokens: Vec<(Token, Span)>,
+    pos: usize,
+    end: usize, // 原文长度，用于“意外结束”的位置
+}
+
+impl Parser {
+    fn peek_op(&self) -> Option<char> {
+        match self.tokens.get(self.pos) {
+            Some((Token::Op(c), _)) => Some(*c),
+            _ => None,
+        }
+    }
+
+    fn binary(&mut self, ops: &str, next: fn(&mut Parser) -> Result<Expr, ExprError>) -> Result<Expr, ExprError> {
+        let mut left = next(self)?;
+        while let Some(op) = self.peek_op().filter(|op| ops.contains(*op)) {
+            self.pos += 1;
+            let right = next(self)?;
+            let span = left.span.to(right.span);
+            left = Expr { kind: ExprKind::Binary(op, Box::new(left), Box::new(right)), span };
+        }
+        Ok(left)
+    }
+
+    fn expr(&mut self) -> Result<Expr, ExprError> {
+        self.binary("+-", Parser::term)
+    }
+
+    fn term(&mut self) -> Result<Expr, ExprError> {
+        self.binary("*/%", Parser::unary)
+    }
+
+    fn unary(&mut self) -> Result<Expr, ExprError> {
+        if self.peek_op() == Some('-') {
+            let start = self.tokens[self.pos].1;
+            self.pos += 1;
+            let inner = self.unary()?;
+            let span = start.to(inner.span);
+            return Ok(Expr { kind: ExprKind::Neg(Box::new(inner)), span });
+        }
+        self.power()
+    }
+
+    fn power(&mut self) -> Result<Expr, ExprError> {
+        let base = self.atom()?;
+        if self.peek_op() != Some('^') {
+            return Ok(base);
+        }
+        self.pos += 1;
+        let exponent = self.unary()?;
+        let span = base.span.to(exponent.span);
+        Ok(Expr { kind: ExprKind::Binary('^', Box::new(base), Box::new(exponent)), span })
+    }
+
+    fn atom(&mut self) -> Result<Expr, ExprError> {
+        let (token, span) = match self.tokens.get(self.pos) {
+            Some(t) => t.clone(),
+            None => return Err(ExprError::new(ExprErrorKind::UnexpectedEnd, Span { start: self.end, end: self.end })),
+        };
+        self.pos += 1;
+        match token {
+            Token::Number(text) => Ok(Expr { kind: ExprKind::Number(text), span }),
+            Token::Ident(name) => Ok(Expr { kind: ExprKind::Var(name), span }),
+            Token::Op('(') => {
+                let inner = self.expr()?;
+                match self.tokens.get(self.pos) {
+                    Some((Token::Op(')'), close)) => {
+                        let close = *close;
+                        self.pos += 1;
+                        Ok(Expr { span: span.to(close), ..inner })
+                    }
+                    _ => Err(ExprError::new(ExprErrorKind::UnclosedParen, span)),
+                }
+            }
+            other => Err(ExprError::new(ExprErrorKind::UnexpectedToken(other.to_string()), span)),
+        }
+    }
+}
+
+pub fn parse(src: &str) -> Result<Expr, ExprError> {
+    let mut parser = Parser { tokens: tokenize(src)?, pos: 0, end: src.len() };
+    let expr = parser.expr()?;
+    // 整个表达式解析完还有剩余的记号，例如 "1 2" 或 "1 + 2)"
+    if let Some((token, span)) = parser.tokens.get(parser.pos) {
+        return Err(ExprError::new(ExprErrorKind::UnexpectedToken(token.to_string()), *span));
+    }
+    Ok(expr)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Integer,
+    Float,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Number::Int(n) => write!(f, "{}", n),
+            Number::Float(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+pub struct Evaluator {
+    mode: Mode,
+    vars: HashMap<String, Number>,
+}
+
+impl Evaluator {
+    pub fn new(mode: Mode) -> Evaluator {
+        Evaluator { mode, vars: HashMap::new() }
+    }
+
+    pub fn var(mut self, name: &str, value: Number) -> Evaluator {
+        self.set(name, value);
+        self
+    }
+
+    pub fn set(&mut self, name: &str, value: Number) {
+        self.vars.insert(name.to_string(), value);
+    }
+
+    pub fn eval(&self, src: &str) -> Result<Number, ExprError> {
+        self.eval_expr(&parse(src)?)
+    }
+
+    pub fn eval_expr(&self, expr: &Expr) -> Result<Number, ExprError> {
+        match self.mode {
+            Mode::Integer => self.eval_int(expr).map(Number::Int),
+            Mode::Float => self.eval_float(expr).map(Number::Float),
+        }
+    }
+
+    fn eval_int(&self, expr: &Expr) -> Result<i64, ExprError> {
+        let err = |kind| ExprError::new(kind, expr.span);
+        match &expr.kind {
+            ExprKind::Number(text) if text.contains('.') => Err(err(ExprErrorKind::FloatInIntegerMode)),
+            // 只由数字组成，解析失败只可能是超出 i64 的范围
+            ExprKind::Number(text) => text.parse().map_err(|_| err(ExprErrorKind::Overflow)),
+            ExprKind::Var(name) => match self.vars.get(name) {
+                Some(Number::Int(n)) => Ok(*n),
+                Some(Number::Float(_)) => Err(err(ExprErrorKind::FloatInIntegerMode)),
+                None => Err(err(ExprErrorKind::UnknownVariable(name.clone()))),
+            },
+            ExprKind::Neg(inner) => self.eval_int(inner)?.checked_neg().ok_or_else(|| err(ExprErrorKind::Overflow)),
+            ExprKind::Binary(op, left, right) => {
+                let (a, b) = (self.eval_int(left)?, self.eval_int(right)?);
+                if b == 0 && (*op == '/' || *op == '%') {
+                    return Err(ExprError::new(ExprErrorKind::DivisionByZero, right.span));
+                }
+                let result = match op {
+                    '+' => a.checked_add(b),
+                    '-' => a.checked_sub(b),
+                    '*' => a.checked_mul(b),
+                    '/' => a.checked_div(b),
+                    '%' => a.checked_rem(b),
+                    _ => {
+                        if b < 0 {
+                            return Err(ExprError::new(ExprErrorKind::NegativeExponent, right.span));
+                        }
+                        u32::try_from(b).ok().and_then(|b| a.checked_pow(b))
+                    }
+                };
+                result.ok_or_else(|| err(ExprErrorKind::Overflow))
+            }
+        }
+    }
+
+    fn eval_float(&self, expr: &Expr) -> Result<f64, ExprError> {
+        let err = |kind| ExprError::new(kind, expr.span);
+        let value = match &expr.kind {
+            ExprKind::Number(text) => text.parse().map_err(|_| err(ExprErrorKind::UnexpectedToken(text.clone())))?,
+            ExprKind::Var(name) => match self.vars.get(name) {
+                Some(Number::Int(n)) => *n as f64,
+                Some(Number::Float(x)) => *x,
+                None => return Err(err(ExprErrorKind::UnknownVariable(name.clone()))),
+            },
+            ExprKind::Neg(inner) => -self.eval_float(inner)?,
+            ExprKind::Binary(op, left, right) => {
+                let (a, b) = (self.eval_float(left)?, self.eval_float(right)?);
+                if b == 0.0 && (*op == '/' || *op == '%') {
+                    return Err(ExprError::new(ExprErrorKind::DivisionByZero, right.span));
+                }
+                match op {
+                    '+' => a + b,
+                    '-' => a - b,
+                    '*' => a * b,
+                    '/' => a / b,
+                    '%' => a % b,
+                    _ => a.powf(b),
+                }
+            }
+        };
+        // inf 和 NaN 都算溢出，例如 10 ^ 400 或 (-8) ^ 0.5
+        if value.is_finite() {
+            Ok(value)
+        } else {
+            Err(err(ExprErrorKind::Overflow))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i18n::Locale;
+
+    fn int(src: &str) -> Result<Number, ExprError> {
+        Evaluator::new(Mode::Integer).eval(src)
+    }
+
+    fn float(src: &str) -> Result<Number, ExprError> {
+        Evaluator::new(Mode::Float).eval(src)
+    }
+
+    #[test]
+    fn precedence_and_associativity() {
+        assert_eq!(int("(1 + 2) * 3 / 4 - -5 ^ 2"), Ok(Number::Int(2 - -25)));
+        assert_eq!(int("2 ^ 3 ^ 2"), Ok(Number::Int(512)));
+        assert_eq!(int("10 - 4 - 3"), Ok(Number::Int(3)));
+        assert_eq!(int("-2 ^ 2"), Ok(Number::Int(-4)));
+        assert_eq!(int("(-2) ^ 2"), Ok(Number::Int(4)));
+        assert_eq!(int("17 % 5 * 2"), Ok(Number::Int(4)));
+        assert_eq!(float("(1 + 2) * 3 / 4 - -5 ^ 2"), Ok(Number::Float(2.25 + 25.0)));
+        assert_eq!(float("2 ^ -1"), Ok(Number::Float(0.5)));
+    }
+
+    #[test]
+    fn variables() {
+        let eval = Evaluator::new(Mode::Float).var("rate", Number::Float(0.5)).var("n", Number::Int(8));
+        assert_eq!(eval.eval("n * rate + 1"), Ok(Number::Float(5.0)));
+        let e = eval.eval("n * ratio").unwrap_err();
+        assert_eq!(e.kind, ExprErrorKind::UnknownVariable("ratio".to_string()));
+        assert_eq!(e.span, Span { start: 4, end: 9 });
+
+        let ints = Evaluator::new(Mode::Integer).var("x", Number::Float(1.5));
+        assert_eq!(ints.eval("x + 1").unwrap_err().kind, ExprErrorKind::FloatInIntegerMode);
+    }
+
+    #[test]
+    fn checked_arithmetic() {
+        assert_eq!(int("9223372036854775807 + 1").unwrap_err().kind, ExprErrorKind::Overflow);
+        assert_eq!(int("99999999999999999999").unwrap_err().kind, ExprErrorKind::Overflow);
+        assert_eq!(int("2 ^ 64").unwrap_err().kind, ExprErrorKind::Overflow);
+        assert_eq!(int("2 ^ -1").unwrap_err().kind, ExprErrorKind::NegativeExponent);
+        assert_eq!(int("1.5 * 2").unwrap_err().kind, ExprErrorKind::FloatInIntegerMode);
+        assert_eq!(float("10 ^ 400").unwrap_err().kind, ExprErrorKind::Overflow);
+        assert_eq!(float("1 / 0").unwrap_err().kind, ExprErrorKind::DivisionByZero);
+    }
+
+    #[test]
+    fn errors_point_at_the_problem() {
+        let src = "1 + 2 / (3 - 3)";
+        let e = int(src).unwrap_err();
+        assert_eq!(e.kind, ExprErrorKind::DivisionByZero);
+        let en = Messages::builtin(Locale::En);
+        assert_eq!(e.diagnostic_in(src, &en), "error: division by zero at 8..15\n  | 1 + 2 / (3 - 3)\n  |         ^^^^^^^");
+        let zh = Messages::builtin(Locale::Zh);
+        assert_eq!(e.diagnostic_in(src, &zh), "错误：除数为零，位置 8..15\n  | 1 + 2 / (3 - 3)\n  |         ^^^^^^^");
+        assert_eq!(e.to_string(), e.message_in(messages()));
+
+        let cases = [
+            ("1 + $", ExprErrorKind::UnexpectedChar('$'), 4),
+            ("1 +", ExprErrorKind::UnexpectedEnd, 3),
+            ("(1 + 2", ExprErrorKind::UnclosedParen, 0),
+            ("1 2", ExprErrorKind::UnexpectedToken("2".to_string()), 2),
+            ("1 * * 2", ExprErrorKind::UnexpectedToken("*".to_string()), 4),
+            ("π + 1 +", ExprErrorKind::UnexpectedEnd, 8),
+        ];
+        for (src, kind, start) in cases {
+            let e = int(src).unwrap_err();
+            assert_eq!((e.kind.clone(), e.span.start), (kind, start), "{}", src);
+        }
+        // 多字节字符前面的对齐按字符计算
+        let src = "π + x";
+        let e = Evaluator::new(Mode::Integer).var("π", Number::Int(3)).eval(src).unwrap_err();
+        assert_eq!(e.span.start, 5);
+        assert!(e.diagnostic(src).ends_with("\n  |     ^"));
+    }
+}
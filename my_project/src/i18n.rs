@@ -0,0 +1,355 @@
+#![allow(dead_code)]
+
+// 中英文消息目录（本地化）
+// 到目前为止，所有 println! 和错误信息里的文字都是直接写在代码里的。这里把面向用户的文字放进消息目录：
+// - 代码里只写消息 ID 和命名占位符，例如 tr!("file.passed", file = a_file)，
+//   目录里写 file.passed = The file passed is: {file}
+// - 目录是简单的 key = value 文本文件，见 my_project/locales/en.txt 和 zh.txt，用 include_str! 编译进程序。
+//   设置环境变量 MY_PROJECT_LOCALE_DIR 后，会用该目录下的 <语言>.txt 覆盖内置的同名消息，改文案不用重新编译
+// - 语言按 POSIX 的优先级从 LC_ALL、LC_MESSAGES、LANG 中取第一个非空的值，zh_CN.UTF-8 之类的得到中文，
+//   其他（包括 C、POSIX）都是英文。当前语言的目录里缺少的消息退回到英文，英文也没有时直接显示消息 ID
+// - 复数：trn!("retry.gave_up", n, ...) 按语言的复数规则选择 retry.gave_up.one 或 retry.gave_up.other，
+//   英文 1 用 one、其他用 other；中文没有单复数变化，总是 other。n 本身可以用 {count} 引用
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+const EN: &str = include_str!("../locales/en.txt");
+const ZH: &str = include_str!("../locales/zh.txt");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Locale {
+    En,
+    Zh,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Plural {
+    One,
+    Other,
+}
+
+impl Locale {
+    // 解析 zh_CN.UTF-8、en_US、C 这样的值
+    pub fn parse(value: &str) -> Locale {
+        if value.to_ascii_lowercase().starts_with("zh") {
+            Locale::Zh
+        } else {
+            Locale::En
+        }
+    }
+
+    pub fn from_env() -> Locale {
+        Locale::from_vars(|name| env::var(name).ok())
+    }
+
+    // 和 from_env 一样，但环境变量从 get 中读取，方便测试
+    pub fn from_vars(get: impl Fn(&str) -> Option<String>) -> Locale {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|name| get(name))
+            .find(|value| !value.is_empty())
+            .map_or(Locale::En, |value| Locale::parse(&value))
+    }
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Zh => "zh",
+        }
+    }
+
+    pub fn plural(self, n: u64) -> Plural {
+        match self {
+            Locale::En if n == 1 => Plural::One,
+            _ => Plural::Other,
+        }
+    }
+
+    fn builtin(self) -> &'static str {
+        match self {
+            Locale::En => EN,
+            Locale::Zh => ZH,
+        }
+    }
+}
+
+impl Plural {
+    fn suffix(self) -> &'static str {
+        match self {
+            Plural::One => "one",
+            Plural::Other => "other",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CatalogError {
+    pub line: usize, // 从 1 开始
+    pub message: String,
+}
+
+impl fmt::Display for CatalogError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for CatalogError {}
+
+#[derive(Debug, Clone, Default)]
+pub struct Catalog {
+    messages: HashMap<String, String>,
+}
+
+impl Catalog {
+    // 每行一条 key = value；空行和 # 开头的行会被忽略。值里的 \n 表示换行，\\ 表示反斜杠
+    pub fn parse(src: &str) -> Result<Catalog, CatalogError> {
+        let mut messages = HashMap::new();
+        for (i, line) in src.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: String| CatalogError { line: i + 1, message };
+            let (key, value) = line.split_once('=').ok_or_else(|| error(format!("expected `key = value`: {}", line)))?;
+            let key = key.trim();
+            if key.is_empty() {
+                return Err(error("empty key".to_string()));
+            }
+            if messages.insert(key.to_string(), unescape(value.trim())).is_some() {
+                return Err(error(format!("duplicate key `{}`", key)));
+            }
+        }
+        Ok(Catalog { messages })
+    }
+
+    pub fn load(path: &Path) -> Result<Catalog, Box<dyn Error + Send + Sync>> {
+        let src = fs::read_to_string(path)?;
+        Ok(Catalog::parse(&src).map_err(|e| format!("{}: {}", path.display(), e))?)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.messages.get(key).map(String::as_str)
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.messages.keys().map(String::as_str)
+    }
+
+    // other 中的消息覆盖同名的消息
+    pub fn merge(&mut self, other: Catalog) {
+        self.messages.extend(other.messages);
+    }
+}
+
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                out.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                out.push('\\');
+                chars.next();
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+pub type Args<'a> = [(&'a str, &'a dyn Display)];
+
+// 替换 {name} 占位符。没有提供的参数原样保留，方便发现目录和代码不一致
+pub fn format(template: &str, args: &Args) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(i) = rest.find(['{', '}']) {
+        out.push_str(&rest[..i]);
+        let tail = &rest[i..];
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            out.push_str(&tail[..1]);
+            rest = &tail[2..];
+            continue;
+        }
+        let placeholder = tail.strip_prefix('{').and_then(|t| t.find('}').map(|end| &t[..end]));
+        match placeholder.and_then(|name| args.iter().find(|(n, _)| *n == name).map(|(_, v)| (name, v))) {
+            Some((name, value)) => {
+                out.push_str(&value.to_string());
+                rest = &tail[name.len() + 2..];
+            }
+            None => {
+                out.push_str(&tail[..1]);
+                rest = &tail[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+pub struct Messages {
+    locale: Locale,
+    catalog: Catalog,
+    fallback: Option<Catalog>, // 当前语言不是英文时，英文目录
+}
+
+impl Messages {
+    pub fn new(locale: Locale, catalog: Catalog) -> Messages {
+        let fallback = match locale {
+            Locale::En => None,
+            _ => Some(Catalog::parse(EN).expect("built-in en catalog")),
+        };
+        Messages { locale, catalog, fallback }
+    }
+
+    pub fn builtin(locale: Locale) -> Messages {
+        let catalog = Catalog::parse(locale.builtin()).expect("built-in catalog");
+        Messages::new(locale, catalog)
+    }
+
+    // 语言来自环境变量，MY_PROJECT_LOCALE_DIR 中的目录文件覆盖内置消息
+    pub fn from_env() -> Messages {
+        let locale = Locale::from_env();
+        let mut messages = Messages::builtin(locale);
+        if let Some(dir) = env::var_os("MY_PROJECT_LOCALE_DIR") {
+            let path = Path::new(&dir).join(format!("{}.txt", locale.code()));
+            match Catalog::load(&path) {
+                Ok(overrides) => messages.catalog.merge(overrides),
+                // 这时全局的 messages() 还没有初始化好，不能用 tr!
+                Err(e) => eprintln!("{}", messages.get("i18n.catalog_ignored", &[("error", &e)])),
+            }
+        }
+        messages
+    }
+
+    pub fn locale(&self) -> Locale {
+        self.locale
+    }
+
+    fn lookup(&self, key: &str) -> Option<&str> {
+        self.catalog.get(key).or_else(|| self.fallback.as_ref().and_then(|c| c.get(key)))
+    }
+
+    pub fn get(&self, id: &str, args: &Args) -> String {
+        format(self.lookup(id).unwrap_or(id), args)
+    }
+
+    // 按 count 选择复数形式，count 可以在消息里用 {count} 引用
+    pub fn plural(&self, id: &str, count: u64, args: &Args) -> String {
+        let key = format!("{}.{}", id, self.locale.plural(count).suffix());
+        // 英文目录里的复数形式要按英文的规则选
+        let fallback_key = format!("{}.{}", id, Locale::En.plural(count).suffix());
+        let template = self
+            .catalog
+            .get(&key)
+            .or_else(|| self.catalog.get(&format!("{}.other", id)))
+            .or_else(|| self.fallback.as_ref().and_then(|c| c.get(&fallback_key)))
+            .unwrap_or(id);
+        let mut all: Vec<(&str, &dyn Display)> = vec![("count", &count)];
+        all.extend_from_slice(args);
+        format(template, &all)
+    }
+}
+
+// 全局的消息目录，第一次使用时根据环境变量加载
+pub fn messages() -> &'static Messages {
+    static MESSAGES: OnceLock<Messages> = OnceLock::new();
+    MESSAGES.get_or_init(Messages::from_env)
+}
+
+// tr!("id") 或 tr!("id", name = value, ...)，返回翻译后的 String
+macro_rules! tr {
+    ($id:expr) => {
+        $crate::i18n::messages().get($id, &[])
+    };
+    ($id:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::messages().get($id, &[$((stringify!($name), &$value as &dyn std::fmt::Display)),+])
+    };
+}
+
+// trn!("id", count) 或 trn!("id", count, name = value, ...)，按 count 选择复数形式
+macro_rules! trn {
+    ($id:expr, $count:expr) => {
+        $crate::i18n::messages().plural($id, $count as u64, &[])
+    };
+    ($id:expr, $count:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::messages().plural($id, $count as u64, &[$((stringify!($name), &$value as &dyn std::fmt::Display)),+])
+    };
+}
+
+pub(crate) use {tr, trn};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_catalogs_and_reports_bad_lines() {
+        let catalog = Catalog::parse("# comment\n\ngreeting = Hello, {name}!\nmulti = a\\nb = c\\\\n\n").unwrap();
+        assert_eq!(catalog.get("greeting"), Some("Hello, {name}!"));
+        assert_eq!(catalog.get("multi"), Some("a\nb = c\\n"));
+        assert_eq!(catalog.get("missing"), None);
+
+        let error = Catalog::parse("a = 1\nno equals sign\n").unwrap_err();
+        assert_eq!(error.to_string(), "line 2: expected `key = value`: no equals sign");
+        assert_eq!(Catalog::parse("a = 1\n a=2").unwrap_err().message, "duplicate key `a`");
+    }
+
+    #[test]
+    fn placeholders() {
+        let args: &Args = &[("file", &"in.txt"), ("n", &7)];
+        assert_eq!(format("{file} has {n} lines", args), "in.txt has 7 lines");
+        assert_eq!(format("{{file}} {missing} {", args), "{file} {missing} {");
+        assert_eq!(format("无占位符", args), "无占位符");
+    }
+
+    #[test]
+    fn locale_from_environment_variables() {
+        let vars = |pairs: &'static [(&'static str, &'static str)]| {
+            move |name: &str| pairs.iter().find(|(n, _)| *n == name).map(|(_, v)| v.to_string())
+        };
+        assert_eq!(Locale::from_vars(vars(&[])), Locale::En);
+        assert_eq!(Locale::from_vars(vars(&[("LANG", "zh_CN.UTF-8")])), Locale::Zh);
+        assert_eq!(Locale::from_vars(vars(&[("LANG", "zh_CN.UTF-8"), ("LC_ALL", "C")])), Locale::En);
+        assert_eq!(Locale::from_vars(vars(&[("LANG", "en_US.UTF-8"), ("LC_ALL", "")])), Locale::En);
+        assert_eq!(Locale::from_vars(vars(&[("LANG", "en_US"), ("LC_MESSAGES", "zh_TW")])), Locale::Zh);
+    }
+
+    #[test]
+    fn builtin_catalogs_plurals_and_fallback() {
+        let (en, zh) = (Messages::builtin(Locale::En), Messages::builtin(Locale::Zh));
+        // 中文目录里的每条消息英文目录里都有
+        let en_keys: Vec<&str> = en.catalog.keys().collect();
+        for key in zh.catalog.keys() {
+            assert!(en_keys.contains(&key), "{} is missing from en.txt", key);
+        }
+
+        let args: &Args = &[("reason", &"timeout"), ("error", &"busy")];
+        assert_eq!(en.plural("retry.gave_up", 1, args), "gave up after 1 attempt (timeout): busy");
+        assert_eq!(en.plural("retry.gave_up", 3, args), "gave up after 3 attempts (timeout): busy");
+        assert_eq!(zh.plural("retry.gave_up", 1, args), "尝试 1 次后放弃（timeout）：busy");
+        assert_eq!(zh.get("num.result", &[("n", &10)]), "你最喜欢的数字一定是 10。");
+        let range: &Args = &[("min", &0), ("max", &150), ("value", &200)];
+        assert_eq!(en.get("validate.in_range", range), "must be between 0 and 150, got 200");
+        assert_eq!(zh.get("validate.in_range", range), "必须在 0 到 150 之间，实际是 200");
+        assert_eq!(en.plural("validate.max_len", 1, &[("len", &3)]), "must be at most 1 character, got 3");
+        assert_eq!(zh.get("error.parse_int", &[]), "无效的整数");
+
+        let mut partial = Catalog::parse("hello = 你好").unwrap();
+        partial.merge(Catalog::parse("cli.help = 帮助").unwrap());
+        let zh = Messages::new(Locale::Zh, partial);
+        assert_eq!(zh.get("cli.help", &[]), "帮助");
+        assert_eq!(zh.get("num.result", &[("n", &10)]), "Your favorite number must be 10.");
+        assert_eq!(zh.plural("retry.gave_up", 1, args), "gave up after 1 attempt (timeout): busy");
+        assert_eq!(zh.get("no.such.message", &[]), "no.such.message");
+    }
+}
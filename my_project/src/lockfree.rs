@@ -0,0 +1,383 @@
+#![allow(dead_code)]
+
+// 无锁（lock-free）数据结构
+// threads.rs 用 mpsc 通道在线程间传递数据，通道和 Mutex<VecDeque> 内部都要加锁，竞争激烈时线程会排队。
+// 这里只用 std::sync::atomic 的 compare_exchange（CAS）实现两种结构：
+// - TreiberStack：无锁栈。push / pop 都是“读 head → 准备新值 → CAS 替换 head”，CAS 失败说明别人抢先了，重试即可
+// - MsQueue：Michael–Scott 无锁队列，多生产者多消费者（MPMC）。
+//   head 指向一个哑节点（dummy），真正的第一个元素在 head.next；tail 可能落后一步，看到时顺手帮它前进
+// 被摘下的节点交给 epoch 模块延迟释放，见 epoch.rs。
+// benchmark 在相同的生产者 / 消费者数量下比较它们和 Mutex<VecDeque>、mpsc 的耗时。
+// 无锁不等于更快：每次操作都要 pin，CAS 失败还要重试，std 的 Mutex 在很多负载下反而更快，以实测为准。
+use std::collections::VecDeque;
+use std::mem::{ManuallyDrop, MaybeUninit};
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::epoch;
+
+// benchmark 用的统一接口
+pub trait ConcurrentQueue<T>: Sync {
+    fn push(&self, value: T);
+    fn pop(&self) -> Option<T>;
+}
+
+struct StackNode<T> {
+    value: ManuallyDrop<T>, // pop 时把值读走，释放节点时不能再 drop 它
+    next: *mut StackNode<T>,
+}
+
+// 节点退休后可能由别的线程释放
+unsafe impl<T: Send> Send for StackNode<T> {}
+
+pub struct TreiberStack<T> {
+    head: AtomicPtr<StackNode<T>>,
+}
+
+unsafe impl<T: Send> Send for TreiberStack<T> {}
+unsafe impl<T: Send> Sync for TreiberStack<T> {}
+
+impl<T> Default for TreiberStack<T> {
+    fn default() -> Self {
+        TreiberStack::new()
+    }
+}
+
+impl<T> TreiberStack<T> {
+    pub fn new() -> TreiberStack<T> {
+        TreiberStack { head: AtomicPtr::new(ptr::null_mut()) }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Acquire).is_null()
+    }
+}
+
+impl<T: Send> TreiberStack<T> {
+    pub fn push(&self, value: T) {
+        let node = Box::into_raw(Box::new(StackNode { value: ManuallyDrop::new(value), next: ptr::null_mut() }));
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            // node 还没有发布出去，可以直接写
+            unsafe { (*node).next = head };
+            match self.head.compare_exchange_weak(head, node, Ordering::Release, Ordering::Relaxed) {
+                Ok(_) => return,
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        let guard = epoch::pin();
+        let mut head = self.head.load(Ordering::Acquire);
+        loop {
+            if head.is_null() {
+                return None;
+            }
+            // SAFETY: pin 期间 head 即使被别人弹出也不会被释放
+            let next = unsafe { (*head).next };
+            match self.head.compare_exchange_weak(head, next, Ordering::AcqRel, Ordering::Acquire) {
+                Ok(_) => unsafe {
+                    let value = ptr::read(&(*head).value);
+                    guard.retire(head);
+                    return Some(ManuallyDrop::into_inner(value));
+                },
+                Err(current) => head = current,
+            }
+        }
+    }
+}
+
+impl<T> Drop for TreiberStack<T> {
+    fn drop(&mut self) {
+        // &mut self 保证没有其他线程在访问，可以直接释放
+        let mut node = *self.head.get_mut();
+        while !node.is_null() {
+            let mut boxed = unsafe { Box::from_raw(node) };
+            unsafe { ManuallyDrop::drop(&mut boxed.value) };
+            node = boxed.next;
+        }
+    }
+}
+
+impl<T: Send> ConcurrentQueue<T> for TreiberStack<T> {
+    fn push(&self, value: T) {
+        TreiberStack::push(self, value)
+    }
+
+    fn pop(&self) -> Option<T> {
+        TreiberStack::pop(self)
+    }
+}
+
+struct QueueNode<T> {
+    value: MaybeUninit<T>, // 哑节点没有值；出队后节点变成新的哑节点，值已被读走
+    next: AtomicPtr<QueueNode<T>>,
+}
+
+impl<T> QueueNode<T> {
+    fn new(value: MaybeUninit<T>) -> *mut QueueNode<T> {
+        Box::into_raw(Box::new(QueueNode { value, next: AtomicPtr::new(ptr::null_mut()) }))
+    }
+}
+
+pub struct MsQueue<T> {
+    head: AtomicPtr<QueueNode<T>>,
+    tail: AtomicPtr<QueueNode<T>>,
+}
+
+unsafe impl<T: Send> Send for MsQueue<T> {}
+unsafe impl<T: Send> Sync for MsQueue<T> {}
+
+impl<T> Default for MsQueue<T> {
+    fn default() -> Self {
+        MsQueue::new()
+    }
+}
+
+impl<T> MsQueue<T> {
+    pub fn new() -> MsQueue<T> {
+        let dummy = QueueNode::new(MaybeUninit::uninit());
+        MsQueue { head: AtomicPtr::new(dummy), tail: AtomicPtr::new(dummy) }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        let _guard = epoch::pin();
+        let head = self.head.load(Ordering::Acquire);
+        unsafe { (*head).next.load(Ordering::Acquire).is_null() }
+    }
+}
+
+impl<T: Send> MsQueue<T> {
+    pub fn push(&self, value: T) {
+        let node = QueueNode::new(MaybeUninit::new(value));
+        let _guard = epoch::pin();
+        loop {
+            let tail = self.tail.load(Ordering::Acquire);
+            // SAFETY: pin 期间 tail 不会被释放
+            let next = unsafe { (*tail).next.load(Ordering::Acquire) };
+            if next.is_null() {
+                let linked = unsafe { &(*tail).next }
+                    .compare_exchange(ptr::null_mut(), node, Ordering::Release, Ordering::Relaxed)
+                    .is_ok();
+                if linked {
+                    // 失败也没关系，说明别的线程已经帮忙把 tail 推进了
+                    let _ = self.tail.compare_exchange(tail, node, Ordering::Release, Ordering::Relaxed);
+                    return;
+                }
+            } else {
+                // tail 落后了，帮它前进一步再重试
+                let _ = self.tail.compare_exchange(tail, next, Ordering::Release, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        let guard = epoch::pin();
+        loop {
+            let head = self.head.load(Ordering::Acquire);
+            let tail = self.tail.load(Ordering::Acquire);
+            let next = unsafe { (*head).next.load(Ordering::Acquire) };
+            if next.is_null() {
+                return None;
+            }
+            if head == tail {
+                // 有新节点但 tail 还没跟上，先推进 tail，保证 head 不会越过 tail
+                let _ = self.tail.compare_exchange(tail, next, Ordering::Release, Ordering::Relaxed);
+                continue;
+            }
+            if self.head.compare_exchange(head, next, Ordering::AcqRel, Ordering::Acquire).is_ok() {
+                unsafe {
+                    // next 成为新的哑节点，只有 CAS 成功的线程会读它的值
+                    let value = (*next).value.assume_init_read();
+                    guard.retire(head);
+                    return Some(value);
+                }
+            }
+        }
+    }
+}
+
+impl<T> Drop for MsQueue<T> {
+    fn drop(&mut self) {
+        let dummy = *self.head.get_mut();
+        let mut node = unsafe { Box::from_raw(dummy) }.next.into_inner();
+        while !node.is_null() {
+            let mut boxed = unsafe { Box::from_raw(node) };
+            unsafe { boxed.value.assume_init_drop() };
+            node = boxed.next.into_inner();
+        }
+    }
+}
+
+impl<T: Send> ConcurrentQueue<T> for MsQueue<T> {
+    fn push(&self, value: T) {
+        MsQueue::push(self, value)
+    }
+
+    fn pop(&self) -> Option<T> {
+        MsQueue::pop(self)
+    }
+}
+
+impl<T: Send> ConcurrentQueue<T> for Mutex<VecDeque<T>> {
+    fn push(&self, value: T) {
+        self.lock().unwrap().push_back(value);
+    }
+
+    fn pop(&self) -> Option<T> {
+        self.lock().unwrap().pop_front()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BenchResult {
+    pub name: &'static str,
+    pub elapsed: Duration,
+}
+
+// producers 个线程各写入 per_producer 个数，consumers 个线程一起读完，返回总耗时。
+// 读到的数之和会被检查，保证没有丢失或重复
+fn run_mpmc<Q: ConcurrentQueue<u64>>(queue: &Q, producers: usize, consumers: usize, per_producer: u64) -> Duration {
+    let total = producers as u64 * per_producer;
+    let consumed = AtomicUsize::new(0);
+    let sum = AtomicUsize::new(0);
+    let started = Instant::now();
+    thread::scope(|s| {
+        for _ in 0..producers {
+            s.spawn(|| (1..=per_producer).for_each(|v| queue.push(v)));
+        }
+        for _ in 0..consumers {
+            s.spawn(|| {
+                while consumed.load(Ordering::SeqCst) < total as usize {
+                    match queue.pop() {
+                        Some(v) => {
+                            sum.fetch_add(v as usize, Ordering::SeqCst);
+                            consumed.fetch_add(1, Ordering::SeqCst);
+                        }
+                        None => thread::yield_now(),
+                    }
+                }
+            });
+        }
+    });
+    let elapsed = started.elapsed();
+    let expected = producers as u64 * per_producer * (per_producer + 1) / 2;
+    assert_eq!(sum.into_inner() as u64, expected, "lost or duplicated items");
+    elapsed
+}
+
+// mpsc 只允许一个接收者，consumers 参数对它不起作用
+fn run_mpsc(producers: usize, per_producer: u64) -> Duration {
+    let (tx, rx) = mpsc::channel::<u64>();
+    let started = Instant::now();
+    thread::scope(|s| {
+        for _ in 0..producers {
+            let tx = tx.clone();
+            s.spawn(move || (1..=per_producer).for_each(|v| tx.send(v).unwrap()));
+        }
+        drop(tx);
+        let sum: u64 = rx.iter().sum();
+        assert_eq!(sum, producers as u64 * per_producer * (per_producer + 1) / 2);
+    });
+    started.elapsed()
+}
+
+pub fn benchmark(producers: usize, consumers: usize, per_producer: u64) -> Vec<BenchResult> {
+    vec![
+        BenchResult {
+            name: "TreiberStack",
+            elapsed: run_mpmc(&TreiberStack::new(), producers, consumers, per_producer),
+        },
+        BenchResult { name: "MsQueue", elapsed: run_mpmc(&MsQueue::new(), producers, consumers, per_producer) },
+        BenchResult {
+            name: "Mutex<VecDeque>",
+            elapsed: run_mpmc(&Mutex::new(VecDeque::new()), producers, consumers, per_producer),
+        },
+        BenchResult { name: "mpsc (1 consumer)", elapsed: run_mpsc(producers, per_producer) },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn stack_is_lifo_and_queue_is_fifo() {
+        let stack = TreiberStack::new();
+        let queue = MsQueue::new();
+        for i in 0..5 {
+            stack.push(i);
+            queue.push(i);
+        }
+        assert_eq!(std::iter::from_fn(|| stack.pop()).collect::<Vec<_>>(), vec![4, 3, 2, 1, 0]);
+        assert_eq!(std::iter::from_fn(|| queue.pop()).collect::<Vec<_>>(), vec![0, 1, 2, 3, 4]);
+        assert!(stack.is_empty() && queue.is_empty());
+    }
+
+    #[test]
+    fn queue_keeps_per_producer_order_under_contention() {
+        let queue = MsQueue::new();
+        let seen = Mutex::new(vec![]);
+        thread::scope(|s| {
+            for p in 0..4u64 {
+                let queue = &queue;
+                s.spawn(move || (0..2000).for_each(|i| queue.push((p, i))));
+            }
+            for _ in 0..4 {
+                s.spawn(|| {
+                    let mut local = vec![];
+                    while local.len() < 2000 {
+                        match queue.pop() {
+                            Some(item) => local.push(item),
+                            None => thread::yield_now(),
+                        }
+                    }
+                    seen.lock().unwrap().push(local);
+                });
+            }
+        });
+        // 每个消费者看到的同一个生产者的元素必须是递增的
+        for local in seen.into_inner().unwrap() {
+            for p in 0..4 {
+                let from_p: Vec<u64> = local.iter().filter(|(q, _)| *q == p).map(|(_, i)| *i).collect();
+                assert!(from_p.windows(2).all(|w| w[0] < w[1]));
+            }
+        }
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn values_are_dropped_exactly_once() {
+        let marker = Arc::new(());
+        {
+            let stack = TreiberStack::new();
+            let queue = MsQueue::new();
+            for _ in 0..100 {
+                stack.push(Arc::clone(&marker));
+                queue.push(Arc::clone(&marker));
+            }
+            for _ in 0..50 {
+                drop(stack.pop());
+                drop(queue.pop());
+            }
+            // 剩下的在结构 drop 时释放
+        }
+        assert_eq!(Arc::strong_count(&marker), 1);
+    }
+
+    #[test]
+    #[ignore] // cargo test --release -- --ignored --nocapture bench
+    fn bench_against_locks_and_channels() {
+        for (producers, consumers) in [(1, 1), (4, 4), (8, 8)] {
+            println!("{} producers / {} consumers, 200000 items each:", producers, consumers);
+            for result in benchmark(producers, consumers, 200_000) {
+                println!("  {:<20} {:>10.2?}", result.name, result.elapsed);
+            }
+        }
+    }
+}
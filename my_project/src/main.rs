@@ -13,41 +13,78 @@ mod tests { // 单元测试写在本模块中，集成测试放在/tests文件
 // 这个函数仅当目标系统是 Linux 的时候才会编译
 #[cfg(target_os = "linux")]
 fn are_you_on_linux() {
-    println!("You are running linux!")
+    println!("{}", tr!("linux.running"))
 }
 
 // 而这个函数仅当目标系统 **不是** Linux 时才会编译
 #[cfg(not(target_os = "linux"))]
 fn are_you_on_linux() {
-    println!("You are *not* running linux!")
+    println!("{}", tr!("linux.not_running"))
 }
 
+// 子模块，分别对应 src/ 下的同名文件
+mod actor;
+mod broadcast;
+mod cancel;
+mod channel;
+mod concurrent_map;
+mod container;
+mod crash;
+mod deadlock;
+mod epoch;
+mod error;
+mod executor;
+mod expr;
+mod i18n;
+mod lockfree;
+mod mapreduce;
+mod ordered;
+mod pool;
+mod ratelimit;
+mod resources;
+mod retry;
+mod scheduler;
+mod scope;
+mod signal;
+mod supervisor;
+mod sync;
+mod validate;
+mod workers;
+
 extern crate clap;
 use clap::{Arg, App};
+use i18n::tr;
 
 // 新项目的入口源文件
 fn main() {
-    println!("Hello, world!");
+    // panic 时写崩溃报告，见 crash.rs
+    crash::install(crash::default_dir());
+    println!("{}", tr!("hello"));
     are_you_on_linux();
     // cfg! 宏：在布尔表达式中使用 cfg!(...)
     if cfg!(target_os = "linux") {
-        println!("Yes. It's definitely linux!");
+        println!("{}", tr!("linux.definitely"));
     } else {
-        println!("Yes. It's definitely *not* linux!");
+        println!("{}", tr!("linux.definitely_not"));
     }
     // 生成帮助: cargo run -- -h
     // 添加参数: cargo run -- -f in -n 5
+    // 中文帮助: LANG=zh_CN.UTF-8 cargo run -- -h，文字来自消息目录，见 i18n.rs
+    let (about, help, version) = (tr!("cli.about"), tr!("cli.help"), tr!("cli.version"));
+    let (file_help, num_help) = (tr!("cli.file.help"), tr!("cli.num.help"));
     let matches = App::new("My Test Program")
                       .version("0.1.0")
                       .author("Liuhz <xxx@qq.com>")
-                      .about("Learn how to parse arguments")
+                      .about(about.as_str())
+                      .help_message(help.as_str())
+                      .version_message(version.as_str())
                       .arg(
                           Arg::with_name("file")
                               .short("f")
                               .long("file")
                               .takes_value(true)
                               .required(false)
-                              .help("A cool file")
+                              .help(file_help.as_str())
                       )
                       .arg(
                           Arg::with_name("num")
@@ -55,20 +92,20 @@ fn main() {
                               .long("number")
                               .takes_value(true) // Specifies that the argument takes a value at run time
                               .required(false)
-                              .help("Five less than your favarite number")
+                              .help(num_help.as_str())
                       )
                       .get_matches();
     
     let a_file = matches.value_of("file").unwrap_or("input.txt");
-    println!("The file passed is: {}", a_file);
+    println!("{}", tr!("file.passed", file = a_file));
     
     let num_str = matches.value_of("num");
     match num_str {
-        None => println!("No idea what your favorite number is."),
+        None => println!("{}", tr!("num.missing")),
         Some(s) => {
             match s.parse::<i32>() {
-                Ok(n) => println!("Your favorite number must be {}.", n + 5),
-                Err(_) => println!("That's not a number! {}", s),
+                Ok(n) => println!("{}", tr!("num.result", n = n + 5)),
+                Err(_) => println!("{}", tr!("num.invalid", input = s)),
             }
         }
     }
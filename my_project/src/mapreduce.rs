@@ -0,0 +1,262 @@
+#![allow(dead_code)]
+
+// 并行 map-reduce
+// threads.rs 的数字求和例子：按空白切分数据，每段一个线程求和，最后把中间结果加起来。
+// 这里把它抽象成通用的 par_map_reduce(input, chunker, workers, map_fn, reduce_fn, identity)：
+// 1. chunker 按输入大小和线程数把输入切成若干块；线程数一般用 default_workers()，即 CPU 核数
+// 2. workers 个线程从共享队列里领取块并执行 map_fn，先做完的线程接着领下一块（负载均衡）
+// 3. 各块的结果按块的原始顺序用 reduce_fn 归约，所以 reduce_fn 只需满足结合律，不要求交换律
+// map_fn panic 时，等所有线程结束后在调用者的线程里重新抛出原来的 panic，和 scope.rs 一样。
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::panic;
+use std::path::Path;
+use std::sync::Mutex;
+use std::thread;
+
+// 每个 worker 大约分到的块数。块比线程多，处理得快的线程可以多做几块。
+const CHUNKS_PER_WORKER: usize = 4;
+
+pub fn default_workers() -> usize {
+    thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+// 根据输入长度和线程数选择块大小，至少为 1
+pub fn chunk_size(len: usize, workers: usize) -> usize {
+    let chunks = workers.max(1) * CHUNKS_PER_WORKER;
+    len.div_ceil(chunks).max(1)
+}
+
+// 把某种输入切分成可以发送到其他线程的块
+pub trait Chunker<I> {
+    type Chunk: Send;
+    fn split(&self, input: I, workers: usize) -> Vec<Self::Chunk>;
+}
+
+// 切片按元素个数切分，块是对原切片的借用
+pub struct Slices;
+
+impl<'a, T: Sync> Chunker<&'a [T]> for Slices {
+    type Chunk = &'a [T];
+    fn split(&self, input: &'a [T], workers: usize) -> Vec<&'a [T]> {
+        input.chunks(chunk_size(input.len(), workers)).collect()
+    }
+}
+
+// 迭代器的长度事先未知，先收集起来再按元素个数分批
+pub struct Batches;
+
+impl<I> Chunker<I> for Batches
+where
+    I: IntoIterator,
+    I::Item: Send,
+{
+    type Chunk = Vec<I::Item>;
+    fn split(&self, input: I, workers: usize) -> Vec<Vec<I::Item>> {
+        let items: Vec<I::Item> = input.into_iter().collect();
+        let size = chunk_size(items.len(), workers);
+        let mut batches = Vec::with_capacity(items.len().div_ceil(size));
+        // 每个元素只移动一次；反复 split_off 会把剩下的部分复制一遍又一遍
+        let mut items = items.into_iter();
+        while items.len() > 0 {
+            batches.push(items.by_ref().take(size).collect());
+        }
+        batches
+    }
+}
+
+// 字符串按字节数切分，但每块都在换行符之后结束，不会把一行拆开
+pub struct Lines;
+
+impl<'a> Chunker<&'a str> for Lines {
+    type Chunk = &'a str;
+    fn split(&self, input: &'a str, workers: usize) -> Vec<&'a str> {
+        let size = chunk_size(input.len(), workers);
+        let mut chunks = vec![];
+        let mut rest = input;
+        while !rest.is_empty() {
+            let mut cut = size.min(rest.len());
+            while !rest.is_char_boundary(cut) {
+                cut += 1; // 不能落在多字节字符的中间
+            }
+            let end = match rest[cut..].find('\n') {
+                Some(i) => cut + i + 1,
+                None => rest.len(),
+            };
+            let (chunk, tail) = rest.split_at(end);
+            chunks.push(chunk);
+            rest = tail;
+        }
+        chunks
+    }
+}
+
+// workers 至少为 1
+pub fn par_map_reduce<I, C, R, M, F>(input: I, chunker: C, workers: usize, map_fn: M, reduce_fn: F, identity: R) -> R
+where
+    C: Chunker<I>,
+    R: Send,
+    M: Fn(C::Chunk) -> R + Sync,
+    F: Fn(R, R) -> R,
+{
+    let workers = workers.max(1);
+    let chunks = chunker.split(input, workers);
+    map_chunks(chunks, workers, &map_fn)
+        .into_iter()
+        .fold(identity, reduce_fn)
+}
+
+// 在 workers 个线程上对每个块执行 map_fn，按块的顺序返回结果
+fn map_chunks<T, R, M>(chunks: Vec<T>, workers: usize, map_fn: &M) -> Vec<R>
+where
+    T: Send,
+    R: Send,
+    M: Fn(T) -> R + Sync,
+{
+    let n = chunks.len();
+    if n <= 1 || workers <= 1 {
+        return chunks.into_iter().map(map_fn).collect();
+    }
+    let queue = Mutex::new(chunks.into_iter().enumerate());
+    let mut results: Vec<(usize, R)> = thread::scope(|s| {
+        let handles: Vec<_> = (0..workers.min(n))
+            .map(|_| {
+                s.spawn(|| {
+                    let mut done = vec![];
+                    loop {
+                        // 只在取块时持有锁，map_fn 在锁外执行
+                        let next = queue.lock().unwrap().next();
+                        match next {
+                            Some((i, chunk)) => done.push((i, map_fn(chunk))),
+                            None => return done,
+                        }
+                    }
+                })
+            })
+            .collect();
+        // 保留 worker 的 panic 信息；其余线程在离开 scope 时 join
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap_or_else(|payload| panic::resume_unwind(payload)))
+            .collect()
+    });
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, r)| r).collect()
+}
+
+// 把文件按字节范围切分，每个范围都在换行符之后结束
+pub fn line_aligned_ranges(path: &Path, workers: usize) -> io::Result<Vec<Range<u64>>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let len = reader.get_ref().metadata()?.len();
+    let size = chunk_size(len as usize, workers) as u64;
+    let mut ranges = vec![];
+    let mut start = 0;
+    let mut skipped = vec![];
+    while start < len {
+        let mut end = (start + size).min(len);
+        if end < len {
+            // 从块的末尾向后读到下一个换行符
+            reader.seek(SeekFrom::Start(end))?;
+            skipped.clear();
+            end += reader.read_until(b'\n', &mut skipped)? as u64;
+        }
+        ranges.push(start..end);
+        start = end;
+    }
+    Ok(ranges)
+}
+
+// 读取文件中的一个字节范围
+pub fn read_range(path: &Path, range: &Range<u64>) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(range.start))?;
+    let mut buf = String::with_capacity((range.end - range.start) as usize);
+    file.take(range.end - range.start).read_to_string(&mut buf)?;
+    Ok(buf)
+}
+
+// 对文件做 map-reduce：每个 worker 自己读取分到的字节范围，map_fn 拿到的是若干完整的行
+pub fn par_map_reduce_file<R, M, F>(path: &Path, workers: usize, map_fn: M, reduce_fn: F, identity: R) -> io::Result<R>
+where
+    R: Send,
+    M: Fn(&str) -> R + Sync,
+    F: Fn(R, R) -> R,
+{
+    let workers = workers.max(1);
+    let ranges = line_aligned_ranges(path, workers)?;
+    let results = map_chunks(ranges, workers, &|range: Range<u64>| {
+        read_range(path, &range).map(|text| map_fn(&text))
+    });
+    let mut acc = identity;
+    for r in results {
+        acc = reduce_fn(acc, r?);
+    }
+    Ok(acc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn sums_slices_and_batches() {
+        let data: Vec<u64> = (1..=10_000).collect();
+        let total = par_map_reduce(&data[..], Slices, default_workers(), |c: &[u64]| c.iter().sum(), |a, b| a + b, 0u64);
+        assert_eq!(total, 50_005_000);
+
+        let total = par_map_reduce(1..=100u32, Batches, 3, |c: Vec<u32>| c.len(), |a, b| a + b, 0);
+        assert_eq!(total, 100);
+
+        let batches = Batches.split(0..10, 1); // 4 块，每块最多 3 个
+        assert_eq!(batches, vec![vec![0, 1, 2], vec![3, 4, 5], vec![6, 7, 8], vec![9]]);
+        assert!(Batches.split(0..0, 4).is_empty());
+    }
+
+    #[test]
+    fn keeps_chunk_order_for_non_commutative_reduce() {
+        let words: Vec<String> = (0..500).map(|i| i.to_string()).collect();
+        let joined = par_map_reduce(&words[..], Slices, 4, |c: &[String]| c.concat(), |a, b| a + &b, String::new());
+        assert_eq!(joined, words.concat());
+    }
+
+    #[test]
+    fn digit_sum_over_lines() {
+        let data = "86967897737416471853297327050364959\n11861322575564723963297542624962850\n".repeat(50);
+        let expected: u32 = data.chars().filter_map(|c| c.to_digit(10)).sum();
+        let digit_sum = |chunk: &str| -> u32 {
+            assert!(chunk.is_empty() || chunk.ends_with('\n'));
+            chunk.chars().filter_map(|c| c.to_digit(10)).sum()
+        };
+        assert_eq!(par_map_reduce(&data[..], Lines, default_workers(), digit_sum, |a, b| a + b, 0), expected);
+    }
+
+    #[test]
+    fn worker_panics_keep_their_payload() {
+        let data: Vec<u32> = (0..100).collect();
+        let result = panic::catch_unwind(|| {
+            par_map_reduce(&data[..], Slices, 4, |c: &[u32]| assert!(!c.contains(&42), "bad record 42"), |_, _| (), ())
+        });
+        let payload = result.unwrap_err();
+        assert!(crate::pool::panic_message(&*payload).contains("bad record 42"));
+    }
+
+    #[test]
+    fn file_ranges_align_to_lines() {
+        let path = std::env::temp_dir().join(format!("mapreduce-test-{}.log", std::process::id()));
+        let text: String = (0..1000).map(|i| format!("line {} of the log\n", i)).collect();
+        fs::write(&path, &text).unwrap();
+
+        let ranges = line_aligned_ranges(&path, 3).unwrap();
+        assert_eq!(ranges.first().unwrap().start, 0);
+        assert_eq!(ranges.last().unwrap().end, text.len() as u64);
+        for r in &ranges {
+            assert!(read_range(&path, r).unwrap().ends_with('\n'));
+        }
+
+        let lines = par_map_reduce_file(&path, 3, |chunk| chunk.lines().count(), |a, b| a + b, 0).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(lines, 1000);
+    }
+}
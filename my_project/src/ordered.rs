@@ -0,0 +1,285 @@
+#![allow(dead_code)]
+
+// 按序收集并发结果
+// threads.rs 里各线程打印 id 的顺序取决于谁先结束；intermediate_sums 有序只是因为按顺序 join 了句柄。
+// 这里的生产者可以在任何线程、以任何顺序提交带序号（sequence number）的结果，
+// 消费者严格按 0, 1, 2, ... 的顺序取出。只有“提前到达”的结果才会被缓存在 BTreeMap 中，
+// 还可以设置窗口（window）：序号超前太多的生产者会被阻塞，缓存大小因此有上限。
+// 如果某个序号迟迟不到，消费者会在超时后收到 Gap 错误，而不是无限等待。
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SendError<T> {
+    Duplicate(u64, T),    // 该序号已经提交过或已经被取走
+    Disconnected(u64, T), // 消费者已经 drop
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecvError {
+    // 期望的序号没有到达，但后面的序号已经到了（或生产者都已结束）
+    Gap { expected: u64, next_available: Option<u64> },
+    // 超时且没有任何缓存的结果
+    Timeout,
+    // 所有生产者都已结束，且结果已全部取出
+    Closed,
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SendError::Duplicate(seq, _) => write!(f, "sequence number {} was already sent", seq),
+            SendError::Disconnected(seq, _) => write!(f, "couldn't send {}: receiver is gone", seq),
+        }
+    }
+}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecvError::Gap { expected, next_available: Some(next) } => {
+                write!(f, "sequence number {} is missing, next available is {}", expected, next)
+            }
+            RecvError::Gap { expected, next_available: None } => {
+                write!(f, "sequence number {} is missing and all producers are done", expected)
+            }
+            RecvError::Timeout => write!(f, "timed out waiting for results"),
+            RecvError::Closed => write!(f, "all results have been received"),
+        }
+    }
+}
+
+impl<T: fmt::Debug> Error for SendError<T> {}
+impl Error for RecvError {}
+
+struct State<T> {
+    next: u64,                 // 下一个要交给消费者的序号
+    pending: BTreeMap<u64, T>, // 提前到达的结果
+    senders: usize,
+    receiver_alive: bool,
+}
+
+struct Inner<T> {
+    state: Mutex<State<T>>,
+    window: Option<u64>,
+    arrived: Condvar,  // 有新结果到达，或生产者全部结束
+    advanced: Condvar, // next 前进了，窗口有了空间
+}
+
+impl<T> Inner<T> {
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap()
+    }
+}
+
+pub struct OrderedSender<T> {
+    inner: Arc<Inner<T>>,
+}
+
+pub struct OrderedReceiver<T> {
+    inner: Arc<Inner<T>>,
+}
+
+// 不限制缓存大小
+pub fn ordered<T>() -> (OrderedSender<T>, OrderedReceiver<T>) {
+    make(None)
+}
+
+// 序号 >= next + window 的 send 会阻塞，直到消费者取走前面的结果
+pub fn ordered_with_window<T>(window: u64) -> (OrderedSender<T>, OrderedReceiver<T>) {
+    assert!(window > 0, "window must be positive");
+    make(Some(window))
+}
+
+fn make<T>(window: Option<u64>) -> (OrderedSender<T>, OrderedReceiver<T>) {
+    let inner = Arc::new(Inner {
+        state: Mutex::new(State {
+            next: 0,
+            pending: BTreeMap::new(),
+            senders: 1,
+            receiver_alive: true,
+        }),
+        window,
+        arrived: Condvar::new(),
+        advanced: Condvar::new(),
+    });
+    (OrderedSender { inner: Arc::clone(&inner) }, OrderedReceiver { inner })
+}
+
+impl<T> OrderedSender<T> {
+    pub fn send(&self, seq: u64, item: T) -> Result<(), SendError<T>> {
+        let mut state = self.inner.lock();
+        loop {
+            if !state.receiver_alive {
+                return Err(SendError::Disconnected(seq, item));
+            }
+            if seq < state.next || state.pending.contains_key(&seq) {
+                return Err(SendError::Duplicate(seq, item));
+            }
+            match self.inner.window {
+                Some(window) if seq >= state.next.saturating_add(window) => {
+                    state = self.inner.advanced.wait(state).unwrap();
+                }
+                _ => break,
+            }
+        }
+        state.pending.insert(seq, item);
+        self.inner.arrived.notify_all();
+        Ok(())
+    }
+}
+
+impl<T> Clone for OrderedSender<T> {
+    fn clone(&self) -> Self {
+        self.inner.lock().senders += 1;
+        OrderedSender { inner: Arc::clone(&self.inner) }
+    }
+}
+
+impl<T> Drop for OrderedSender<T> {
+    fn drop(&mut self) {
+        let mut state = self.inner.lock();
+        state.senders -= 1;
+        if state.senders == 0 {
+            self.inner.arrived.notify_all();
+        }
+    }
+}
+
+impl<T> OrderedReceiver<T> {
+    // 下一个要取出的序号
+    pub fn next_seq(&self) -> u64 {
+        self.inner.lock().next
+    }
+
+    // 缓存中等待前面序号的结果个数
+    pub fn buffered(&self) -> usize {
+        self.inner.lock().pending.len()
+    }
+
+    // 阻塞直到下一个序号到达；生产者全部结束时返回 Closed 或 Gap
+    pub fn recv(&self) -> Result<T, RecvError> {
+        self.recv_deadline(None)
+    }
+
+    // 最多等待 timeout。超时时如果后面的序号已经到达，返回 Gap 而不是 Timeout
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvError> {
+        self.recv_deadline(Some(Instant::now() + timeout))
+    }
+
+    fn recv_deadline(&self, deadline: Option<Instant>) -> Result<T, RecvError> {
+        let mut state = self.inner.lock();
+        loop {
+            let next = state.next;
+            if let Some(item) = state.pending.remove(&next) {
+                state.next += 1;
+                self.inner.advanced.notify_all();
+                return Ok(item);
+            }
+            let gap = || RecvError::Gap {
+                expected: next,
+                next_available: state.pending.keys().next().copied(),
+            };
+            if state.senders == 0 {
+                return Err(if state.pending.is_empty() { RecvError::Closed } else { gap() });
+            }
+            state = match deadline {
+                None => self.inner.arrived.wait(state).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(if state.pending.is_empty() { RecvError::Timeout } else { gap() });
+                    }
+                    self.inner.arrived.wait_timeout(state, deadline - now).unwrap().0
+                }
+            };
+        }
+    }
+
+    // 放弃缺失的序号，从 seq 继续（通常是 Gap 中的 next_available）
+    pub fn skip_to(&self, seq: u64) {
+        let mut state = self.inner.lock();
+        if seq > state.next {
+            state.next = seq;
+            state.pending = state.pending.split_off(&seq);
+            self.inner.advanced.notify_all();
+        }
+    }
+
+    // 按序迭代，直到生产者全部结束；遇到缺口时停止
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        std::iter::from_fn(move || self.recv().ok())
+    }
+}
+
+impl<T> Drop for OrderedReceiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.inner.lock();
+        state.receiver_alive = false;
+        state.pending.clear();
+        self.inner.advanced.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn releases_results_in_sequence_order() {
+        let (tx, rx) = ordered_with_window(4);
+        let producers: Vec<_> = (0..4u64)
+            .map(|p| {
+                let tx = tx.clone();
+                // 每个线程负责 p, p+4, p+8, ...，处理速度各不相同
+                thread::spawn(move || {
+                    for seq in (p..100).step_by(4) {
+                        thread::sleep(Duration::from_micros((100 - seq) * 10 * (p + 1)));
+                        tx.send(seq, seq * 10).unwrap();
+                    }
+                })
+            })
+            .collect();
+        drop(tx);
+        let received: Vec<u64> = rx.iter().collect();
+        for p in producers {
+            p.join().unwrap();
+        }
+        assert_eq!(received, (0..100).map(|s| s * 10).collect::<Vec<_>>());
+        assert_eq!(rx.recv(), Err(RecvError::Closed));
+    }
+
+    #[test]
+    fn huge_window_does_not_overflow() {
+        let (tx, rx) = ordered_with_window(u64::MAX);
+        tx.send(0, 'a').unwrap();
+        assert_eq!(rx.recv(), Ok('a'));
+        tx.send(u64::MAX - 1, 'z').unwrap();
+        tx.send(1, 'b').unwrap();
+        assert_eq!(rx.recv(), Ok('b'));
+    }
+
+    #[test]
+    fn reports_gap_and_timeout() {
+        let (tx, rx) = ordered();
+        assert_eq!(rx.recv_timeout(Duration::from_millis(5)), Err(RecvError::Timeout));
+        tx.send(0, "a").unwrap();
+        tx.send(2, "c").unwrap();
+        assert_eq!(tx.send(2, "c"), Err(SendError::Duplicate(2, "c")));
+        assert_eq!(rx.recv(), Ok("a"));
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(5)),
+            Err(RecvError::Gap { expected: 1, next_available: Some(2) })
+        );
+        assert_eq!(rx.buffered(), 1);
+        rx.skip_to(2);
+        assert_eq!(rx.recv(), Ok("c"));
+        tx.send(4, "e").unwrap();
+        drop(tx);
+        assert_eq!(rx.recv(), Err(RecvError::Gap { expected: 3, next_available: Some(4) }));
+    }
+}
@@ -0,0 +1,236 @@
+#![allow(dead_code)]
+
+// 线程池
+// threads.rs 为每个任务都 spawn 一个新的操作系统线程，任务多而小的时候，创建线程的开销远大于任务本身。
+// 线程池预先创建固定数量的工作线程（worker），它们从共享队列中取出装箱的闭包（Box<dyn FnOnce>）执行。
+// 队列由 Mutex 保护，空闲的 worker 在 Condvar 上等待新任务。
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+thread_local! {
+    // 当前线程嵌套在几层 run_catching 里
+    static CATCHING: Cell<usize> = const { Cell::new(0) };
+}
+
+struct State {
+    jobs: VecDeque<Job>,
+    shutdown: bool,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    available: Condvar,
+}
+
+pub struct ThreadPool {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+// submit 提交的任务没能产生结果的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobError {
+    Panicked(String), // 任务 panic 了，附带 panic 信息
+    Cancelled,        // 线程池被 shutdown_now 关闭，任务还没来得及执行
+}
+
+impl fmt::Display for JobError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JobError::Panicked(msg) => write!(f, "job panicked: {}", msg),
+            JobError::Cancelled => write!(f, "job was cancelled before it ran"),
+        }
+    }
+}
+
+impl std::error::Error for JobError {}
+
+// 用于等待 submit 结果的句柄
+pub struct JobHandle<T> {
+    rx: Receiver<Result<T, JobError>>,
+}
+
+impl<T> JobHandle<T> {
+    // 阻塞直到任务结束。任务被丢弃时发送端也随之 drop，recv 返回 Err，即 Cancelled。
+    pub fn wait(self) -> Result<T, JobError> {
+        self.rx.recv().unwrap_or(Err(JobError::Cancelled))
+    }
+}
+
+// panic 的负载（payload）通常是 &str 或 String
+pub(crate) fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "Box<dyn Any>".to_string()
+    }
+}
+
+// 执行 f 并捕获它的 panic，用在捕获之后会把 panic 交给调用者处理的地方（返回 Err、重启 actor……）。
+// 执行期间 panic_is_caught() 返回 true，crash.rs 的钩子据此跳过这些 panic，不为它们写崩溃报告。
+// 之后要用 resume_unwind 重新抛出的地方不能用它：resume_unwind 不调用钩子，这个 panic 就再也不会有报告
+pub(crate) fn run_catching<R>(f: impl FnOnce() -> R) -> thread::Result<R> {
+    CATCHING.with(|depth| depth.set(depth.get() + 1));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    CATCHING.with(|depth| depth.set(depth.get() - 1));
+    result
+}
+
+// 在 panic 钩子里调用：当前的 panic 是否会被 run_catching 捕获
+pub(crate) fn panic_is_caught() -> bool {
+    CATCHING.with(Cell::get) > 0
+}
+
+impl ThreadPool {
+    // 创建有 size 个工作线程的线程池，size 必须大于 0
+    pub fn new(size: usize) -> ThreadPool {
+        assert!(size > 0, "thread pool needs at least one worker");
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State { jobs: VecDeque::new(), shutdown: false }),
+            available: Condvar::new(),
+        });
+        let workers = (0..size)
+            .map(|i| {
+                let shared = Arc::clone(&shared);
+                thread::Builder::new()
+                    .name(format!("pool-worker-{}", i))
+                    .spawn(move || worker_loop(&shared))
+                    .expect("failed to spawn worker thread")
+            })
+            .collect();
+        ThreadPool { shared, workers }
+    }
+
+    pub fn size(&self) -> usize {
+        self.workers.len()
+    }
+
+    // 提交一个不关心结果的任务（fire-and-forget）
+    pub fn execute<F>(&self, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let mut state = self.shared.state.lock().unwrap();
+        state.jobs.push_back(Box::new(f));
+        self.shared.available.notify_one();
+    }
+
+    // 提交一个有返回值的任务，通过返回的 JobHandle 等待结果
+    pub fn submit<F, T>(&self, f: F) -> JobHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+        self.execute(move || {
+            let result = run_catching(f).map_err(|payload| JobError::Panicked(panic_message(&*payload)));
+            let _ = tx.send(result); // 调用者可能已经不再等待结果
+        });
+        JobHandle { rx }
+    }
+
+    // 立即关闭：丢弃队列中尚未开始的任务，只等待正在执行的任务结束
+    pub fn shutdown_now(mut self) {
+        let dropped: Vec<Job> = {
+            let mut state = self.shared.state.lock().unwrap();
+            state.shutdown = true;
+            state.jobs.drain(..).collect()
+        };
+        drop(dropped); // 在锁外 drop，任务闭包的析构函数可能比较耗时
+        self.join_workers();
+    }
+
+    fn join_workers(&mut self) {
+        self.shared.state.lock().unwrap().shutdown = true;
+        self.shared.available.notify_all();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for ThreadPool {
+    // 优雅关闭：worker 会先执行完队列中剩余的任务再退出
+    fn drop(&mut self) {
+        self.join_workers();
+    }
+}
+
+fn worker_loop(shared: &Shared) {
+    loop {
+        let job = {
+            let mut state = shared.state.lock().unwrap();
+            loop {
+                if let Some(job) = state.jobs.pop_front() {
+                    break job;
+                }
+                if state.shutdown {
+                    return;
+                }
+                state = shared.available.wait(state).unwrap();
+            }
+        };
+        // 某个任务 panic 不应该让 worker 线程退出
+        let _ = panic::catch_unwind(AssertUnwindSafe(job));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[test]
+    fn runs_many_small_jobs() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        {
+            let pool = ThreadPool::new(4);
+            for _ in 0..10_000 {
+                let counter = Arc::clone(&counter);
+                pool.execute(move || {
+                    counter.fetch_add(1, Ordering::Relaxed);
+                });
+            }
+        } // drop 时等待所有任务完成
+        assert_eq!(counter.load(Ordering::Relaxed), 10_000);
+    }
+
+    #[test]
+    fn submit_returns_results_and_panics() {
+        let pool = ThreadPool::new(2);
+        let handles: Vec<_> = (0..10u32).map(|i| pool.submit(move || i * i)).collect();
+        let squares: Vec<u32> = handles.into_iter().map(|h| h.wait().unwrap()).collect();
+        assert_eq!(squares, (0..10).map(|i| i * i).collect::<Vec<_>>());
+
+        let bad = pool.submit(|| -> u32 { panic!("bad input") });
+        assert_eq!(bad.wait(), Err(JobError::Panicked("bad input".to_string())));
+        // worker 依然可用
+        assert_eq!(pool.submit(|| 7).wait(), Ok(7));
+    }
+
+    #[test]
+    fn shutdown_now_cancels_pending_jobs() {
+        let pool = ThreadPool::new(1);
+        let (started_tx, started_rx) = mpsc::channel();
+        let blocker = pool.submit(move || {
+            started_tx.send(()).unwrap();
+            thread::sleep(Duration::from_millis(100));
+            1
+        });
+        let pending = pool.submit(|| 2);
+        started_rx.recv().unwrap();
+        pool.shutdown_now();
+        assert_eq!(blocker.wait(), Ok(1));
+        assert_eq!(pending.wait(), Err(JobError::Cancelled));
+    }
+}
@@ -0,0 +1,315 @@
+#![allow(dead_code)]
+
+// 线程安全的限流器（rate limiter）
+// process.rs 一次只启动一个子进程，如果在循环里启动就需要控制频率；调用本地的模拟服务也一样。
+// 这里提供两种算法：
+// - TokenBucket（令牌桶）：桶里最多放 burst 个令牌，每秒补充 rate 个，每次请求取走 n 个，允许短时间的突发
+// - SlidingWindow（滑动窗口日志）：记录最近 window 时间内每次放行的时间点，窗口内最多放行 limit 个
+// RateLimiter 用 Mutex 保护算法的状态（和 threads.rs 中的计数器一样），提供三种获取方式：
+// try_acquire 立即返回，acquire 一直等待，acquire_timeout 最多等待一段时间。
+// KeyedLimiter 为每个 key（例如服务名、用户 ID）分别维护一个限流器。
+// 时间和等待都通过 scheduler::Clock，测试时可以用 MockClock 得到确定的结果。
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::scheduler::{Clock, SystemClock};
+
+// 限流算法：能放行就扣减并返回 Ok，否则返回还需要等待多久
+pub trait Algorithm: Send {
+    fn try_take(&mut self, n: u32, now: Instant) -> Result<(), Duration>;
+
+    // 一次最多能获取多少个许可
+    fn capacity(&self) -> u32;
+}
+
+pub struct TokenBucket {
+    rate: f64, // 每秒补充的令牌数
+    burst: u32,
+    tokens: f64,
+    last: Option<Instant>,
+}
+
+impl TokenBucket {
+    // 初始时桶是满的
+    pub fn new(rate: f64, burst: u32) -> TokenBucket {
+        assert!(rate > 0.0, "rate must be positive");
+        TokenBucket { rate, burst, tokens: burst as f64, last: None }
+    }
+}
+
+impl Algorithm for TokenBucket {
+    fn try_take(&mut self, n: u32, now: Instant) -> Result<(), Duration> {
+        if let Some(last) = self.last {
+            let elapsed = now.saturating_duration_since(last).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * self.rate).min(self.burst as f64);
+        }
+        self.last = Some(now);
+        let n = n as f64;
+        if self.tokens >= n {
+            self.tokens -= n;
+            Ok(())
+        } else {
+            // rate 很小时等待时间可能超出 Duration 的范围，按最大值算
+            Err(Duration::try_from_secs_f64((n - self.tokens) / self.rate).unwrap_or(Duration::MAX))
+        }
+    }
+
+    fn capacity(&self) -> u32 {
+        self.burst
+    }
+}
+
+pub struct SlidingWindow {
+    limit: u32,
+    window: Duration,
+    log: VecDeque<Instant>, // 窗口内每个许可的放行时间，从旧到新
+}
+
+impl SlidingWindow {
+    pub fn new(limit: u32, window: Duration) -> SlidingWindow {
+        SlidingWindow { limit, window, log: VecDeque::new() }
+    }
+}
+
+impl Algorithm for SlidingWindow {
+    fn try_take(&mut self, n: u32, now: Instant) -> Result<(), Duration> {
+        while self.log.front().is_some_and(|t| now.saturating_duration_since(*t) >= self.window) {
+            self.log.pop_front();
+        }
+        let over = (self.log.len() + n as usize).saturating_sub(self.limit as usize);
+        if over == 0 {
+            self.log.extend(std::iter::repeat_n(now, n as usize));
+            Ok(())
+        } else {
+            // 最旧的 over 个记录滑出窗口之后才有足够的空间
+            Err((self.log[over - 1] + self.window).saturating_duration_since(now))
+        }
+    }
+
+    fn capacity(&self) -> u32 {
+        self.limit
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitError {
+    TooLarge { requested: u32, capacity: u32 }, // 永远不可能满足的请求
+    Timeout,
+}
+
+impl fmt::Display for RateLimitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RateLimitError::TooLarge { requested, capacity } => {
+                write!(f, "requested {} permits but the limiter allows at most {}", requested, capacity)
+            }
+            RateLimitError::Timeout => write!(f, "timed out waiting for permits"),
+        }
+    }
+}
+
+impl Error for RateLimitError {}
+
+pub struct RateLimiter<A> {
+    algorithm: Mutex<A>,
+    clock: Arc<dyn Clock>,
+}
+
+impl<A: Algorithm> RateLimiter<A> {
+    pub fn new(algorithm: A) -> RateLimiter<A> {
+        RateLimiter::with_clock(algorithm, Arc::new(SystemClock))
+    }
+
+    pub fn with_clock(algorithm: A, clock: Arc<dyn Clock>) -> RateLimiter<A> {
+        RateLimiter { algorithm: Mutex::new(algorithm), clock }
+    }
+
+    // 返回 Ok 表示已放行，Err 中是还需要等待的时间
+    fn take(&self, n: u32) -> Result<Result<(), Duration>, RateLimitError> {
+        let mut algorithm = self.algorithm.lock().unwrap();
+        let capacity = algorithm.capacity();
+        if n > capacity {
+            return Err(RateLimitError::TooLarge { requested: n, capacity });
+        }
+        Ok(algorithm.try_take(n, self.clock.now()))
+    }
+
+    // Ok(false) 表示现在没有足够的许可；n 超过容量时和 acquire 一样返回 TooLarge
+    pub fn try_acquire(&self, n: u32) -> Result<bool, RateLimitError> {
+        Ok(self.take(n)?.is_ok())
+    }
+
+    pub fn acquire(&self, n: u32) -> Result<(), RateLimitError> {
+        // 等待时不持有锁；醒来后可能被别的线程抢先，所以要重新尝试
+        while let Err(wait) = self.take(n)? {
+            self.clock.sleep(wait);
+        }
+        Ok(())
+    }
+
+    pub fn acquire_timeout(&self, n: u32, timeout: Duration) -> Result<(), RateLimitError> {
+        let deadline = self.clock.now() + timeout;
+        loop {
+            let wait = match self.take(n)? {
+                Ok(()) => return Ok(()),
+                Err(wait) => wait,
+            };
+            let remaining = deadline.saturating_duration_since(self.clock.now());
+            if wait > remaining {
+                return Err(RateLimitError::Timeout); // 等到截止时间也不够，不必白等
+            }
+            self.clock.sleep(wait);
+        }
+    }
+}
+
+// 按 key 分别限流，第一次见到某个 key 时用 make 创建它的算法状态
+pub struct KeyedLimiter<K, A> {
+    limiters: RwLock<HashMap<K, Arc<RateLimiter<A>>>>,
+    make: Box<dyn Fn() -> A + Send + Sync>,
+    clock: Arc<dyn Clock>,
+}
+
+impl<K: Hash + Eq + Clone, A: Algorithm> KeyedLimiter<K, A> {
+    pub fn new(make: impl Fn() -> A + Send + Sync + 'static) -> KeyedLimiter<K, A> {
+        KeyedLimiter::with_clock(make, Arc::new(SystemClock))
+    }
+
+    pub fn with_clock(make: impl Fn() -> A + Send + Sync + 'static, clock: Arc<dyn Clock>) -> KeyedLimiter<K, A> {
+        KeyedLimiter { limiters: RwLock::new(HashMap::new()), make: Box::new(make), clock }
+    }
+
+    // 大多数调用只需要读锁；key 不存在时才升级为写锁插入
+    pub fn limiter(&self, key: &K) -> Arc<RateLimiter<A>> {
+        if let Some(limiter) = self.limiters.read().unwrap().get(key) {
+            return Arc::clone(limiter);
+        }
+        let mut limiters = self.limiters.write().unwrap();
+        let limiter = limiters
+            .entry(key.clone())
+            .or_insert_with(|| Arc::new(RateLimiter::with_clock((self.make)(), Arc::clone(&self.clock))));
+        Arc::clone(limiter)
+    }
+
+    pub fn try_acquire(&self, key: &K, n: u32) -> Result<bool, RateLimitError> {
+        self.limiter(key).try_acquire(n)
+    }
+
+    pub fn acquire(&self, key: &K, n: u32) -> Result<(), RateLimitError> {
+        self.limiter(key).acquire(n)
+    }
+
+    pub fn acquire_timeout(&self, key: &K, n: u32, timeout: Duration) -> Result<(), RateLimitError> {
+        self.limiter(key).acquire_timeout(n, timeout)
+    }
+
+    pub fn remove(&self, key: &K) {
+        self.limiters.write().unwrap().remove(key);
+    }
+
+    pub fn len(&self) -> usize {
+        self.limiters.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scheduler::MockClock;
+    use std::thread;
+
+    #[test]
+    fn token_bucket_allows_bursts_then_refills() {
+        let clock = MockClock::new();
+        let limiter = RateLimiter::with_clock(TokenBucket::new(2.0, 5), Arc::new(clock.clone()));
+        assert_eq!(limiter.try_acquire(5), Ok(true));
+        assert_eq!(limiter.try_acquire(1), Ok(false));
+        clock.advance(Duration::from_millis(500)); // 补充 1 个
+        assert_eq!(limiter.try_acquire(1), Ok(true));
+        assert_eq!(limiter.try_acquire(1), Ok(false));
+        clock.advance(Duration::from_secs(60)); // 最多补满 burst 个
+        assert_eq!(limiter.try_acquire(5), Ok(true));
+        assert_eq!(limiter.acquire(6), Err(RateLimitError::TooLarge { requested: 6, capacity: 5 }));
+        assert_eq!(limiter.try_acquire(6), Err(RateLimitError::TooLarge { requested: 6, capacity: 5 }));
+    }
+
+    #[test]
+    fn tiny_rates_do_not_overflow_the_wait() {
+        let clock = MockClock::new();
+        let limiter = RateLimiter::with_clock(TokenBucket::new(1e-300, 1), Arc::new(clock.clone()));
+        assert_eq!(limiter.try_acquire(1), Ok(true));
+        assert_eq!(limiter.try_acquire(1), Ok(false));
+        assert_eq!(limiter.acquire_timeout(1, Duration::from_secs(3600)), Err(RateLimitError::Timeout));
+    }
+
+    #[test]
+    fn sliding_window_counts_recent_permits() {
+        let clock = MockClock::new();
+        let limiter = RateLimiter::with_clock(SlidingWindow::new(3, Duration::from_secs(10)), Arc::new(clock.clone()));
+        assert_eq!(limiter.try_acquire(2), Ok(true));
+        clock.advance(Duration::from_secs(6));
+        assert_eq!(limiter.try_acquire(1), Ok(true));
+        assert_eq!(limiter.try_acquire(1), Ok(false));
+        clock.advance(Duration::from_secs(4)); // 前两个滑出窗口
+        assert_eq!(limiter.try_acquire(2), Ok(true));
+        assert_eq!(limiter.try_acquire(1), Ok(false));
+    }
+
+    #[test]
+    fn acquire_waits_on_the_injected_clock() {
+        let clock = MockClock::new();
+        let start = clock.now();
+        let limiter = RateLimiter::with_clock(TokenBucket::new(10.0, 1), Arc::new(clock.clone()));
+        assert_eq!(limiter.acquire(1), Ok(())); // 初始的令牌，不用等
+        // 下一个令牌要 100ms 后才有
+        assert_eq!(limiter.acquire_timeout(1, Duration::from_millis(50)), Err(RateLimitError::Timeout));
+        assert_eq!(clock.now() - start, Duration::ZERO);
+        assert_eq!(limiter.acquire_timeout(1, Duration::from_millis(150)), Ok(()));
+        assert_eq!(clock.now() - start, Duration::from_millis(100));
+        assert_eq!(limiter.acquire(1), Ok(()));
+        assert_eq!(clock.now() - start, Duration::from_millis(200));
+    }
+
+    #[test]
+    fn blocking_acquire_is_throttled_across_threads() {
+        let limiter = Arc::new(RateLimiter::new(TokenBucket::new(100.0, 1)));
+        let started = Instant::now();
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let limiter = Arc::clone(&limiter);
+                thread::spawn(move || {
+                    for _ in 0..5 {
+                        limiter.acquire(1).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        // 20 个许可，第一个来自初始的令牌，其余每 10ms 一个
+        assert!(started.elapsed() >= Duration::from_millis(180));
+        assert_eq!(limiter.acquire_timeout(1, Duration::ZERO), Err(RateLimitError::Timeout));
+        assert_eq!(limiter.acquire_timeout(1, Duration::from_secs(1)), Ok(()));
+    }
+
+    #[test]
+    fn keyed_limiters_are_independent() {
+        let clock = MockClock::new();
+        let limiter = KeyedLimiter::with_clock(|| SlidingWindow::new(1, Duration::from_secs(1)), Arc::new(clock));
+        assert_eq!(limiter.try_acquire(&"db", 1), Ok(true));
+        assert_eq!(limiter.try_acquire(&"db", 1), Ok(false));
+        assert_eq!(limiter.try_acquire(&"cache", 1), Ok(true));
+        assert_eq!(limiter.len(), 2);
+        limiter.remove(&"db");
+        assert_eq!(limiter.try_acquire(&"db", 1), Ok(true));
+    }
+}
@@ -0,0 +1,182 @@
+#![allow(dead_code)]
+
+// 子进程的资源统计与限制
+// Child::wait 只能拿到退出状态。这里用 wait4 回收子进程，同时拿到内核记录的 rusage：
+// 用户态 / 内核态 CPU 时间、最大常驻内存（max RSS），再加上我们自己测量的墙钟时间。
+// 启动之前还可以在 pre_exec 中调用 setrlimit，限制 CPU 秒数、地址空间、打开文件数和输出文件大小。
+use std::io;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::process::{Child, Command, ExitStatus};
+use std::time::{Duration, Instant};
+
+// 已结束子进程的资源使用情况
+#[derive(Debug, Clone, Copy)]
+pub struct ResourceUsage {
+    pub status: ExitStatus,
+    pub user_time: Duration,
+    pub system_time: Duration,
+    pub max_rss_bytes: u64,
+    pub wall_time: Duration,
+}
+
+fn timeval_to_duration(tv: libc::timeval) -> Duration {
+    Duration::from_secs(tv.tv_sec as u64) + Duration::from_micros(tv.tv_usec as u64)
+}
+
+// Linux 上 ru_maxrss 的单位是 KB，macOS 上则是字节
+#[cfg(target_os = "macos")]
+fn maxrss_to_bytes(maxrss: libc::c_long) -> u64 {
+    maxrss as u64
+}
+
+#[cfg(not(target_os = "macos"))]
+fn maxrss_to_bytes(maxrss: libc::c_long) -> u64 {
+    maxrss as u64 * 1024
+}
+
+// 等待子进程结束并收集资源使用情况。
+// wait4 会回收（reap）子进程，之后不能再对同一个 Child 调用 wait，所以这里按值接收 Child。
+// `started` 是调用 spawn 之前记录的时刻，用于计算墙钟时间。
+pub fn wait_with_usage(child: Child, started: Instant) -> io::Result<ResourceUsage> {
+    let pid = child.id() as libc::pid_t;
+    let mut status: libc::c_int = 0;
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    loop {
+        let ret = unsafe { libc::wait4(pid, &mut status, 0, &mut usage) };
+        if ret == pid {
+            break;
+        }
+        let err = io::Error::last_os_error();
+        // 被信号打断时重试
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+    Ok(ResourceUsage {
+        status: ExitStatus::from_raw(status),
+        user_time: timeval_to_duration(usage.ru_utime),
+        system_time: timeval_to_duration(usage.ru_stime),
+        max_rss_bytes: maxrss_to_bytes(usage.ru_maxrss),
+        wall_time: started.elapsed(),
+    })
+}
+
+// 启动命令并等待它结束，返回资源使用情况
+pub fn run_measured(cmd: &mut Command) -> io::Result<ResourceUsage> {
+    let started = Instant::now();
+    let child = cmd.spawn()?;
+    wait_with_usage(child, started)
+}
+
+// 子进程的资源上限，None 表示沿用父进程的设置。
+// 每一项都同时设置软限制和硬限制，子进程无法再调高。
+// CPU 的硬限制多留 1 秒：到达软限制时先收到 SIGXCPU，到达硬限制时才被 SIGKILL。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ResourceLimits {
+    cpu_secs: Option<u64>,
+    address_space_bytes: Option<u64>,
+    open_files: Option<u64>,
+    file_size_bytes: Option<u64>,
+}
+
+impl ResourceLimits {
+    pub fn new() -> ResourceLimits {
+        ResourceLimits::default()
+    }
+
+    // 超过后内核发送 SIGXCPU
+    pub fn cpu_secs(mut self, secs: u64) -> ResourceLimits {
+        self.cpu_secs = Some(secs);
+        self
+    }
+
+    // 超过后内存分配失败
+    pub fn address_space(mut self, bytes: u64) -> ResourceLimits {
+        self.address_space_bytes = Some(bytes);
+        self
+    }
+
+    // 超过后 open 返回 EMFILE
+    pub fn open_files(mut self, n: u64) -> ResourceLimits {
+        self.open_files = Some(n);
+        self
+    }
+
+    // 超过后写文件收到 SIGXFSZ
+    pub fn file_size(mut self, bytes: u64) -> ResourceLimits {
+        self.file_size_bytes = Some(bytes);
+        self
+    }
+
+    // 在 fork 之后、exec 之前设置限制。
+    // pre_exec 的闭包运行在子进程里，只能调用异步信号安全的函数，setrlimit 满足要求。
+    pub fn apply(&self, cmd: &mut Command) {
+        let limits = *self;
+        unsafe {
+            cmd.pre_exec(move || limits.set_all());
+        }
+    }
+
+    // 在 fork 之后的子进程里运行，不能 panic：cpu_secs 为 u64::MAX 时硬限制也不能溢出
+    fn set_all(&self) -> io::Result<()> {
+        set_limit(libc::RLIMIT_CPU, self.cpu_secs, self.cpu_secs.map(|s| s.saturating_add(1)))?;
+        set_limit(libc::RLIMIT_AS, self.address_space_bytes, self.address_space_bytes)?;
+        set_limit(libc::RLIMIT_NOFILE, self.open_files, self.open_files)?;
+        set_limit(libc::RLIMIT_FSIZE, self.file_size_bytes, self.file_size_bytes)?;
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+type Resource = libc::__rlimit_resource_t;
+#[cfg(not(target_os = "linux"))]
+type Resource = libc::c_int;
+
+fn set_limit(resource: Resource, soft: Option<u64>, hard: Option<u64>) -> io::Result<()> {
+    let (soft, hard) = match (soft, hard) {
+        (Some(soft), Some(hard)) => (soft as libc::rlim_t, hard as libc::rlim_t),
+        _ => return Ok(()),
+    };
+    let limit = libc::rlimit { rlim_cur: soft, rlim_max: hard };
+    if unsafe { libc::setrlimit(resource, &limit) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sh(script: &str) -> Command {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(script);
+        cmd
+    }
+
+    #[test]
+    fn measures_exit_status_and_times() {
+        let usage = run_measured(&mut sh("i=0; while [ $i -lt 20000 ]; do i=$((i+1)); done; exit 3")).unwrap();
+        assert_eq!(usage.status.code(), Some(3));
+        assert!(usage.user_time + usage.system_time > Duration::from_millis(0));
+        assert!(usage.wall_time >= usage.user_time);
+        assert!(usage.max_rss_bytes > 0);
+    }
+
+    #[test]
+    fn open_files_limit_is_applied() {
+        let mut cmd = sh("ulimit -n");
+        ResourceLimits::new().open_files(16).apply(&mut cmd);
+        let output = cmd.output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "16");
+    }
+
+    #[test]
+    fn cpu_limit_kills_busy_loop() {
+        let mut cmd = sh("while :; do :; done");
+        ResourceLimits::new().cpu_secs(1).apply(&mut cmd);
+        let usage = run_measured(&mut cmd).unwrap();
+        assert_eq!(usage.status.signal(), Some(libc::SIGXCPU));
+        assert!(usage.user_time + usage.system_time >= Duration::from_millis(900));
+    }
+}
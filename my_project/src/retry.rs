@@ -0,0 +1,305 @@
+#![allow(dead_code)]
+
+// 失败重试
+// exceptions.rs 讲了 `?` 和 Result：出错就向上返回。但有些错误是暂时的，例如 CI 机器上文件正被占用、
+// 启动子进程时碰到 ETXTBSY（可执行文件还在被写入）。这类操作隔一会儿再试一次往往就成功了。
+// retry(&policy, || op()) 反复调用 op，直到成功或者放弃。放弃的条件：
+// - 错误不可重试（retry_if 返回 false），例如文件根本不存在
+// - 已经尝试了 max_attempts 次
+// - 下一次重试会超过总的截止时间（deadline）
+// 两次尝试之间的等待时间有三种策略：
+// - Fixed：固定间隔
+// - Exponential：指数退避，沿用 supervisor.rs 的 Backoff（initial * multiplier^n，不超过 max）
+// - DecorrelatedJitter：去相关抖动，下一次等待在 [base, 上一次等待 * 3] 之间随机取值且不超过 cap，
+//   避免大量客户端在同一时刻一起重试
+// on_attempt 在每次失败后调用，可以用来打日志。RetryError 的说明文字来自消息目录（i18n.rs）。
+use std::collections::hash_map::RandomState;
+use std::error::Error;
+use std::fmt;
+use std::hash::BuildHasher;
+use std::io;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::i18n::{messages, Messages};
+use crate::supervisor::Backoff;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Strategy {
+    Fixed(Duration),
+    Exponential(Backoff),
+    DecorrelatedJitter { base: Duration, cap: Duration },
+}
+
+// 一次失败的尝试，交给 on_attempt
+pub struct Attempt<'a, E> {
+    pub number: u32, // 从 1 开始
+    pub error: &'a E,
+    pub elapsed: Duration,
+    pub next_delay: Option<Duration>, // None 表示不再重试
+}
+
+type AttemptHook<E> = Box<dyn Fn(&Attempt<E>)>;
+
+pub struct RetryPolicy<E> {
+    strategy: Strategy,
+    max_attempts: u32,
+    deadline: Option<Duration>,
+    retry_if: Box<dyn Fn(&E) -> bool>,
+    on_attempt: Option<AttemptHook<E>>,
+}
+
+impl<E> RetryPolicy<E> {
+    // 默认最多尝试 3 次，所有错误都重试
+    pub fn new(strategy: Strategy) -> RetryPolicy<E> {
+        RetryPolicy { strategy, max_attempts: 3, deadline: None, retry_if: Box::new(|_| true), on_attempt: None }
+    }
+
+    pub fn fixed(delay: Duration) -> RetryPolicy<E> {
+        RetryPolicy::new(Strategy::Fixed(delay))
+    }
+
+    pub fn exponential(backoff: Backoff) -> RetryPolicy<E> {
+        RetryPolicy::new(Strategy::Exponential(backoff))
+    }
+
+    pub fn decorrelated_jitter(base: Duration, cap: Duration) -> RetryPolicy<E> {
+        RetryPolicy::new(Strategy::DecorrelatedJitter { base, cap })
+    }
+
+    pub fn max_attempts(mut self, attempts: u32) -> RetryPolicy<E> {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    // 从第一次尝试开始计算的总时限
+    pub fn deadline(mut self, deadline: Duration) -> RetryPolicy<E> {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    pub fn retry_if(mut self, retryable: impl Fn(&E) -> bool + 'static) -> RetryPolicy<E> {
+        self.retry_if = Box::new(retryable);
+        self
+    }
+
+    pub fn on_attempt(mut self, hook: impl Fn(&Attempt<E>) + 'static) -> RetryPolicy<E> {
+        self.on_attempt = Some(Box::new(hook));
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    NotRetryable,
+    MaxAttempts,
+    Deadline,
+}
+
+// 放弃重试时返回最后一次的错误
+#[derive(Debug)]
+pub struct RetryError<E> {
+    pub error: E,
+    pub attempts: u32,
+    pub reason: StopReason,
+}
+
+impl<E: fmt::Display> RetryError<E> {
+    // 用指定的消息目录生成说明文字；Display 用的是全局的 messages()
+    pub fn message_in(&self, messages: &Messages) -> String {
+        let reason = match self.reason {
+            StopReason::NotRetryable => messages.get("retry.not_retryable", &[]),
+            StopReason::MaxAttempts => messages.get("retry.max_attempts", &[]),
+            StopReason::Deadline => messages.get("retry.deadline", &[]),
+        };
+        messages.plural("retry.gave_up", u64::from(self.attempts), &[("reason", &reason), ("error", &self.error)])
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for RetryError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message_in(messages()))
+    }
+}
+
+impl<E: Error + 'static> Error for RetryError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+// 不引入 rand，用 xorshift 生成抖动用的随机数，种子来自 RandomState
+struct Jitter(u64);
+
+impl Jitter {
+    fn new() -> Jitter {
+        Jitter(RandomState::new().hash_one(Instant::now()) | 1)
+    }
+
+    // [low, high] 之间的随机时长
+    fn between(&mut self, low: Duration, high: Duration) -> Duration {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        let span = high.saturating_sub(low).as_nanos() as u64;
+        low + Duration::from_nanos(if span == 0 { 0 } else { self.0 % (span + 1) })
+    }
+}
+
+// 依次产生每次重试前的等待时间
+struct Delays {
+    strategy: Strategy,
+    retries: u32,
+    previous: Duration,
+    jitter: Jitter,
+}
+
+impl Delays {
+    fn new(strategy: Strategy) -> Delays {
+        let previous = match strategy {
+            Strategy::DecorrelatedJitter { base, .. } => base,
+            _ => Duration::ZERO,
+        };
+        Delays { strategy, retries: 0, previous, jitter: Jitter::new() }
+    }
+}
+
+impl Iterator for Delays {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        let delay = match self.strategy {
+            Strategy::Fixed(delay) => delay,
+            Strategy::Exponential(backoff) => backoff.delay(self.retries),
+            Strategy::DecorrelatedJitter { base, cap } => {
+                let high = self.previous.saturating_mul(3).max(base);
+                self.jitter.between(base, high).min(cap)
+            }
+        };
+        self.retries += 1;
+        self.previous = delay;
+        Some(delay)
+    }
+}
+
+pub fn retry<T, E>(policy: &RetryPolicy<E>, mut op: impl FnMut() -> Result<T, E>) -> Result<T, RetryError<E>> {
+    let started = Instant::now();
+    let mut delays = Delays::new(policy.strategy);
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let error = match op() {
+            Ok(value) => return Ok(value),
+            Err(error) => error,
+        };
+        let delay = delays.next().unwrap_or_default();
+        let stop = if !(policy.retry_if)(&error) {
+            Some(StopReason::NotRetryable)
+        } else if attempts >= policy.max_attempts {
+            Some(StopReason::MaxAttempts)
+        } else if policy.deadline.is_some_and(|d| started.elapsed() + delay > d) {
+            // 等完这一次就超时了，不如现在放弃
+            Some(StopReason::Deadline)
+        } else {
+            None
+        };
+        if let Some(hook) = &policy.on_attempt {
+            hook(&Attempt {
+                number: attempts,
+                error: &error,
+                elapsed: started.elapsed(),
+                next_delay: if stop.is_none() { Some(delay) } else { None },
+            });
+        }
+        if let Some(reason) = stop {
+            return Err(RetryError { error, attempts, reason });
+        }
+        thread::sleep(delay);
+    }
+}
+
+// 常见的暂时性 io 错误，可以直接传给 retry_if
+pub fn is_transient_io(error: &io::Error) -> bool {
+    use io::ErrorKind::*;
+    match error.kind() {
+        Interrupted | WouldBlock | TimedOut | ConnectionRefused | ConnectionReset | ConnectionAborted | BrokenPipe => true,
+        // EBUSY：设备或文件正忙；ETXTBSY：要执行的文件还在被写入，CI 里启动刚编译好的程序时常见
+        _ => matches!(error.raw_os_error(), Some(libc::EAGAIN) | Some(libc::EBUSY) | Some(libc::ETXTBSY)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i18n::Locale;
+    use std::cell::{Cell, RefCell};
+    use std::process::Command;
+    use std::rc::Rc;
+
+    #[test]
+    fn succeeds_after_transient_failures_and_reports_attempts() {
+        let log = Rc::new(RefCell::new(vec![]));
+        let log2 = Rc::clone(&log);
+        let policy = RetryPolicy::fixed(Duration::from_millis(1))
+            .max_attempts(5)
+            .retry_if(is_transient_io)
+            .on_attempt(move |a: &Attempt<io::Error>| log2.borrow_mut().push((a.number, a.next_delay)));
+        let calls = Cell::new(0);
+        let result = retry(&policy, || {
+            calls.set(calls.get() + 1);
+            if calls.get() < 3 {
+                Err(io::Error::from_raw_os_error(libc::ETXTBSY))
+            } else {
+                Ok("spawned")
+            }
+        });
+        assert_eq!(result.unwrap(), "spawned");
+        let one_ms = Some(Duration::from_millis(1));
+        assert_eq!(*log.borrow(), vec![(1, one_ms), (2, one_ms)]);
+    }
+
+    #[test]
+    fn stops_for_permanent_errors_limits_and_deadlines() {
+        let spawn = RetryPolicy::fixed(Duration::from_millis(1)).max_attempts(5).retry_if(is_transient_io);
+        let attempts = Cell::new(0);
+        let error = retry(&spawn, || {
+            attempts.set(attempts.get() + 1);
+            Command::new("/definitely/not/a/program").spawn()
+        })
+        .unwrap_err();
+        assert_eq!((error.reason, error.attempts, attempts.get()), (StopReason::NotRetryable, 1, 1));
+        assert_eq!(error.error.kind(), io::ErrorKind::NotFound);
+
+        let limited = RetryPolicy::fixed(Duration::ZERO).max_attempts(4);
+        let error = retry(&limited, || Err::<(), _>("flaky")).unwrap_err();
+        assert_eq!((error.reason, error.attempts), (StopReason::MaxAttempts, 4));
+        let en = error.message_in(&Messages::builtin(Locale::En));
+        assert_eq!(en, "gave up after 4 attempts (no attempts left): flaky");
+        assert_eq!(error.message_in(&Messages::builtin(Locale::Zh)), "尝试 4 次后放弃（已用完尝试次数）：flaky");
+        assert_eq!(error.to_string(), error.message_in(messages()));
+
+        let started = Instant::now();
+        let bounded = RetryPolicy::fixed(Duration::from_millis(20)).max_attempts(100).deadline(Duration::from_millis(50));
+        let error = retry(&bounded, || Err::<(), _>("slow")).unwrap_err();
+        assert_eq!(error.reason, StopReason::Deadline);
+        // 最多在 0ms、20ms、40ms 各一次；机器繁忙时 sleep 会多睡，次数可能更少
+        assert!(error.attempts >= 1 && error.attempts <= 3, "{}", error.attempts);
+        // 只保证不会为了重试而睡过截止时间，这里的上限留足余量，不依赖调度的精度
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn backoff_strategies() {
+        let take = |strategy| Delays::new(strategy).take(6).map(|d| d.as_millis()).collect::<Vec<_>>();
+        assert_eq!(take(Strategy::Fixed(Duration::from_millis(5))), vec![5; 6]);
+        let backoff = Backoff { initial: Duration::from_millis(10), max: Duration::from_millis(100), multiplier: 2 };
+        assert_eq!(take(Strategy::Exponential(backoff)), vec![10, 20, 40, 80, 100, 100]);
+
+        let (base, cap) = (Duration::from_millis(10), Duration::from_millis(200));
+        let mut previous = base;
+        for delay in Delays::new(Strategy::DecorrelatedJitter { base, cap }).take(1000) {
+            assert!(delay >= base && delay <= cap && delay <= previous * 3);
+            previous = delay;
+        }
+    }
+}
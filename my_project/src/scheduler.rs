@@ -0,0 +1,439 @@
+#![allow(dead_code)]
+
+// 定时任务调度器
+// threads.rs 用 thread::sleep 计时，process.rs 运行 `sleep 5s`，都只能等待一件事。
+// Scheduler 用一个二叉堆（BinaryHeap，按到期时间排序的小顶堆）保存所有待执行的任务，
+// 一个后台线程等到堆顶任务到期就执行它；周期任务执行完再按规则放回堆中。
+// 周期任务有两种模式：
+// - FixedRate：按固定频率执行，下一次的时间 = 上一次的计划时间 + interval（落后太多时跳过错过的轮次，不会连续补跑）
+// - FixedDelay：两次执行之间保持固定间隔，下一次的时间 = 上一次执行结束的时间 + interval
+// 时间来自 Clock trait。测试时使用 MockClock 手动拨动时间，再调用 run_pending，结果是确定的。
+use std::cmp::Ordering as CmpOrdering;
+use std::collections::BinaryHeap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::i18n::tr;
+use crate::pool::panic_message;
+
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+
+    // 等待一段时间，需要睡眠的调用者（例如 ratelimit.rs 的 acquire）通过它等待
+    fn sleep(&self, duration: Duration);
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+// 只有调用 advance 时才前进的时钟；sleep 不真的等待，而是直接把时钟拨快
+#[derive(Clone)]
+pub struct MockClock {
+    base: Instant,
+    offset: Arc<Mutex<Duration>>,
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        MockClock::new()
+    }
+}
+
+impl MockClock {
+    pub fn new() -> MockClock {
+        MockClock { base: Instant::now(), offset: Arc::new(Mutex::new(Duration::ZERO)) }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.offset.lock().unwrap() += by;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.base + *self.offset.lock().unwrap()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    FixedRate,
+    FixedDelay,
+}
+
+type Job = Box<dyn FnMut() + Send>;
+
+struct Entry {
+    due: Instant,
+    seq: u64, // 到期时间相同时按提交顺序执行
+    id: u64,  // 任务的编号，周期任务每次重新入堆时不变
+    job: Job,
+    repeat: Option<(Duration, Mode)>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        (self.due, self.seq) == (other.due, other.seq)
+    }
+}
+
+impl Eq for Entry {}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entry {
+    // 反转比较，让 BinaryHeap 的堆顶是最早到期的任务
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        (other.due, other.seq).cmp(&(self.due, self.seq))
+    }
+}
+
+struct State {
+    heap: BinaryHeap<Entry>,
+    next_seq: u64,
+    shutdown: bool,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+    clock: Arc<dyn Clock>,
+}
+
+// 用于取消已安排的任务
+#[derive(Clone)]
+pub struct JobHandle {
+    id: u64,
+    cancelled: Arc<AtomicBool>,
+    shared: Arc<Shared>,
+}
+
+impl JobHandle {
+    // 取消后任务不会再执行；如果正在执行，本次执行结束后不再安排下一次
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        let mut state = self.shared.state.lock().unwrap();
+        state.heap.retain(|e| !Arc::ptr_eq(&e.cancelled, &self.cancelled));
+        self.shared.changed.notify_all();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    // 和日志里的任务编号一致
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+pub struct Scheduler {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Scheduler::new()
+    }
+}
+
+impl Scheduler {
+    // 使用系统时钟，并启动后台调度线程
+    pub fn new() -> Scheduler {
+        let mut scheduler = Scheduler::manual(Arc::new(SystemClock));
+        let shared = Arc::clone(&scheduler.shared);
+        scheduler.thread = Some(
+            thread::Builder::new()
+                .name("scheduler".to_string())
+                .spawn(move || run_loop(&shared))
+                .expect("failed to spawn scheduler thread"),
+        );
+        scheduler
+    }
+
+    // 不启动后台线程，由调用者在合适的时候调用 run_pending（配合 MockClock 做确定性测试）
+    pub fn manual(clock: Arc<dyn Clock>) -> Scheduler {
+        Scheduler {
+            shared: Arc::new(Shared {
+                state: Mutex::new(State { heap: BinaryHeap::new(), next_seq: 0, shutdown: false }),
+                changed: Condvar::new(),
+                clock,
+            }),
+            thread: None,
+        }
+    }
+
+    // delay 之后执行一次
+    pub fn schedule_once<F>(&self, delay: Duration, job: F) -> JobHandle
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let mut job = Some(job);
+        self.schedule(delay, None, move || {
+            if let Some(job) = job.take() {
+                job();
+            }
+        })
+    }
+
+    // 每隔 interval 执行一次，第一次在 interval 之后
+    pub fn schedule_every<F>(&self, interval: Duration, mode: Mode, job: F) -> JobHandle
+    where
+        F: FnMut() + Send + 'static,
+    {
+        assert!(interval > Duration::ZERO, "interval must be positive");
+        self.schedule(interval, Some((interval, mode)), job)
+    }
+
+    fn schedule<F>(&self, delay: Duration, repeat: Option<(Duration, Mode)>, job: F) -> JobHandle
+    where
+        F: FnMut() + Send + 'static,
+    {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let due = self.shared.clock.now() + delay;
+        let mut state = self.shared.state.lock().unwrap();
+        // 第一次入堆的序号同时作为任务编号
+        let seq = state.next_seq;
+        state.next_seq += 1;
+        let entry = Entry { due, seq, id: seq, job: Box::new(job), repeat, cancelled: Arc::clone(&cancelled) };
+        state.heap.push(entry);
+        self.shared.changed.notify_all();
+        JobHandle { id: seq, cancelled, shared: Arc::clone(&self.shared) }
+    }
+
+    // 执行所有已经到期的任务，返回执行的个数
+    pub fn run_pending(&self) -> usize {
+        run_pending(&self.shared)
+    }
+
+    pub fn pending(&self) -> usize {
+        self.shared.state.lock().unwrap().heap.len()
+    }
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().shutdown = true;
+        self.shared.changed.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run_pending(shared: &Shared) -> usize {
+    let mut ran = 0;
+    loop {
+        let mut entry = {
+            let mut state = shared.state.lock().unwrap();
+            let now = shared.clock.now();
+            match state.heap.peek() {
+                Some(top) if top.due <= now => state.heap.pop().unwrap(),
+                _ => return ran,
+            }
+        };
+        if entry.cancelled.load(Ordering::SeqCst) {
+            continue;
+        }
+        // 在锁外执行任务，任务里也可以继续安排新任务；单个任务 panic 不影响调度线程。
+        // 没有人处理这个 panic，所以不用 pool::run_catching，crash.rs 的钩子照常为它写报告
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(&mut entry.job)) {
+            eprintln!("{}", tr!("scheduler.job_panicked", id = entry.id, message = panic_message(&*payload)));
+        }
+        ran += 1;
+        let (interval, mode) = match entry.repeat {
+            Some(repeat) => repeat,
+            None => continue,
+        };
+        let now = shared.clock.now();
+        entry.due = match mode {
+            Mode::FixedDelay => now + interval,
+            Mode::FixedRate => {
+                let mut next = entry.due + interval;
+                while next <= now {
+                    next += interval; // 跳过已经错过的轮次
+                }
+                next
+            }
+        };
+        let mut state = shared.state.lock().unwrap();
+        // cancel 先设置标记再加锁清理堆。必须在持有锁之后检查：否则 cancel 可能在检查和放回之间完成清理，
+        // 已取消的任务又被放回堆里，一直执行下去
+        if entry.cancelled.load(Ordering::SeqCst) {
+            continue;
+        }
+        entry.seq = state.next_seq;
+        state.next_seq += 1;
+        state.heap.push(entry);
+    }
+}
+
+fn run_loop(shared: &Shared) {
+    loop {
+        run_pending(shared);
+        let state = shared.state.lock().unwrap();
+        if state.shutdown {
+            return;
+        }
+        // 等到堆顶任务到期，或者有新任务 / 取消 / 关闭
+        match state.heap.peek().map(|top| top.due) {
+            Some(due) => {
+                let timeout = due.saturating_duration_since(shared.clock.now());
+                drop(shared.changed.wait_timeout(state, timeout).unwrap());
+            }
+            None => {
+                drop(shared.changed.wait(state).unwrap());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Log = Arc<Mutex<Vec<String>>>;
+
+    fn recorder() -> (Log, impl Fn(&str) -> Box<dyn FnMut() + Send>) {
+        let log = Arc::new(Mutex::new(vec![]));
+        let log2 = Arc::clone(&log);
+        let make = move |name: &str| -> Box<dyn FnMut() + Send> {
+            let log = Arc::clone(&log2);
+            let name = name.to_string();
+            Box::new(move || log.lock().unwrap().push(name.clone()))
+        };
+        (log, make)
+    }
+
+    #[test]
+    fn once_jobs_run_in_due_order() {
+        let clock = MockClock::new();
+        let scheduler = Scheduler::manual(Arc::new(clock.clone()));
+        let (log, job) = recorder();
+        scheduler.schedule_once(Duration::from_secs(2), job("b"));
+        scheduler.schedule_once(Duration::from_secs(1), job("a"));
+        let cancelled = scheduler.schedule_once(Duration::from_secs(1), job("never"));
+        cancelled.cancel();
+
+        assert_eq!(scheduler.run_pending(), 0);
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(scheduler.run_pending(), 1);
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(scheduler.run_pending(), 1);
+        assert_eq!(*log.lock().unwrap(), vec!["a", "b"]);
+        assert_eq!(scheduler.pending(), 0);
+    }
+
+    #[test]
+    fn panicking_jobs_keep_their_schedule() {
+        let clock = MockClock::new();
+        let scheduler = Scheduler::manual(Arc::new(clock.clone()));
+        let runs = Arc::new(Mutex::new(0));
+        let counter = Arc::clone(&runs);
+        let once = scheduler.schedule_once(Duration::from_secs(1), || {});
+        let flaky = scheduler.schedule_every(Duration::from_secs(1), Mode::FixedRate, move || {
+            *counter.lock().unwrap() += 1;
+            panic!("flaky job");
+        });
+        assert_ne!(once.id(), flaky.id());
+        for _ in 0..3 {
+            clock.advance(Duration::from_secs(1));
+            scheduler.run_pending();
+        }
+        assert_eq!(*runs.lock().unwrap(), 3);
+        assert_eq!(scheduler.pending(), 1);
+    }
+
+    #[test]
+    fn fixed_rate_and_fixed_delay() {
+        let clock = MockClock::new();
+        let scheduler = Scheduler::manual(Arc::new(clock.clone()));
+        let runs = Arc::new(Mutex::new(vec![]));
+        let start = clock.now();
+        for (name, mode) in [("rate", Mode::FixedRate), ("delay", Mode::FixedDelay)] {
+            let (runs, clock) = (Arc::clone(&runs), clock.clone());
+            scheduler.schedule_every(Duration::from_secs(10), mode, move || {
+                runs.lock().unwrap().push((name, (clock.now() - start).as_secs()));
+                clock.advance(Duration::from_secs(3)); // 每次执行耗时 3 秒
+            });
+        }
+        for _ in 0..3 {
+            while scheduler.run_pending() > 0 {}
+            clock.advance(Duration::from_secs(1));
+        }
+        for _ in 0..40 {
+            scheduler.run_pending();
+            clock.advance(Duration::from_secs(1));
+        }
+        let runs = runs.lock().unwrap();
+        let times = |which| runs.iter().filter(|(n, _)| *n == which).map(|(_, t)| *t).collect::<Vec<_>>();
+        // rate 在第 10 秒触发，下一次计划在 20、30 ... 秒（执行耗时不影响节奏）
+        assert_eq!(times("rate")[..3].iter().map(|t| t / 10).collect::<Vec<_>>(), vec![1, 2, 3]);
+        // delay 每两次之间至少隔 10 + 3 秒
+        let delay = times("delay");
+        assert!(delay.windows(2).all(|w| w[1] - w[0] >= 13));
+    }
+
+    #[test]
+    fn background_thread_runs_and_cancels_jobs() {
+        let scheduler = Scheduler::new();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let ticks = scheduler.schedule_every(Duration::from_millis(5), Mode::FixedRate, move || {
+            let _ = tx.send(());
+        });
+        for _ in 0..3 {
+            rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        }
+        ticks.cancel();
+        while rx.recv_timeout(Duration::from_millis(50)).is_ok() {}
+        assert!(ticks.is_cancelled());
+        assert_eq!(scheduler.pending(), 0);
+    }
+
+    #[test]
+    fn cancel_from_another_thread_while_job_runs() {
+        let clock = MockClock::new();
+        let scheduler = Scheduler::manual(Arc::new(clock.clone()));
+        let (started_tx, started_rx) = std::sync::mpsc::channel();
+        let (resume_tx, resume_rx) = std::sync::mpsc::channel::<()>();
+        let resume_rx = Mutex::new(resume_rx);
+        let handle = scheduler.schedule_every(Duration::from_secs(1), Mode::FixedRate, move || {
+            started_tx.send(()).unwrap();
+            let _ = resume_rx.lock().unwrap().recv(); // 执行到一半，等待取消完成
+        });
+        clock.advance(Duration::from_secs(1));
+        thread::scope(|s| {
+            let runner = s.spawn(|| scheduler.run_pending());
+            started_rx.recv().unwrap();
+            let canceller = handle.clone();
+            s.spawn(move || canceller.cancel()).join().unwrap();
+            resume_tx.send(()).unwrap();
+            assert_eq!(runner.join().unwrap(), 1);
+        });
+        assert_eq!(scheduler.pending(), 0);
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(scheduler.run_pending(), 0);
+    }
+}
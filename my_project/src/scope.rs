@@ -0,0 +1,160 @@
+#![allow(dead_code)]
+
+// 作用域线程（scoped threads）
+// threads.rs 里的 data 恰好是 &'static str，move 闭包才能捕获 data_segment。
+// thread::spawn 要求闭包是 'static 的；如果数据是运行时读入的 String 或 Vec，只能把每一块 clone 进线程。
+// 作用域线程保证在 scope 返回之前所有 worker 都已经结束，所以 worker 可以直接借用栈上的数据。
+// 与 std::thread::scope 不同的是：某个 worker panic 时，这里会在所有 worker 都 join 之后，
+// 用原始的 panic 负载（payload）重新 panic，调用者能看到真正的错误信息，而不是 "a scoped thread panicked"。
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+type Payload = Box<dyn Any + Send + 'static>;
+type Slot = Arc<Mutex<Option<Payload>>>;
+
+pub struct Scope<'scope, 'env: 'scope> {
+    inner: &'scope thread::Scope<'scope, 'env>,
+    // 每个 worker 一个槽位，worker panic 时把负载放进去
+    slots: Arc<Mutex<Vec<Slot>>>,
+}
+
+pub struct ScopedJoinHandle<'scope, T> {
+    inner: thread::ScopedJoinHandle<'scope, Option<T>>,
+    slot: Slot,
+}
+
+impl<'scope, 'env> Scope<'scope, 'env> {
+    // 启动一个可以借用 'env 数据的 worker
+    pub fn spawn<F, T>(&self, f: F) -> ScopedJoinHandle<'scope, T>
+    where
+        F: FnOnce() -> T + Send + 'scope,
+        T: Send + 'scope,
+    {
+        let slot: Slot = Arc::new(Mutex::new(None));
+        self.slots.lock().unwrap().push(Arc::clone(&slot));
+        let worker_slot = Arc::clone(&slot);
+        // 不用 pool::run_catching：这个 panic 之后会被 resume_unwind 重新抛出，而 resume_unwind 不调用 panic 钩子，
+        // 只有在这里让钩子照常运行，crash.rs 才能为它写报告
+        let inner = self.inner.spawn(move || match panic::catch_unwind(AssertUnwindSafe(f)) {
+            Ok(value) => Some(value),
+            Err(payload) => {
+                *worker_slot.lock().unwrap() = Some(payload);
+                None
+            }
+        });
+        ScopedJoinHandle { inner, slot }
+    }
+}
+
+impl<'scope, T> ScopedJoinHandle<'scope, T> {
+    // 手动 join 时由调用者处理 panic，scope 结束时就不再重新抛出它
+    pub fn join(self) -> thread::Result<T> {
+        match self.inner.join() {
+            Ok(Some(value)) => Ok(value),
+            Ok(None) => Err(self.slot.lock().unwrap().take().expect("panicked worker left no payload")),
+            Err(payload) => Err(payload),
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.inner.is_finished()
+    }
+}
+
+// 创建一个作用域，f 返回后等待所有 worker 结束。
+// 如果有 worker panic 且没有被手动 join，按启动顺序把第一个 panic 重新抛出。
+pub fn scope<'env, F, T>(f: F) -> T
+where
+    F: for<'scope> FnOnce(&Scope<'scope, 'env>) -> T,
+{
+    let slots = Arc::new(Mutex::new(vec![]));
+    let result = thread::scope(|s| f(&Scope { inner: s, slots: Arc::clone(&slots) }));
+    let first_panic = slots
+        .lock()
+        .unwrap()
+        .iter()
+        .find_map(|slot| slot.lock().unwrap().take());
+    if let Some(payload) = first_panic {
+        panic::resume_unwind(payload);
+    }
+    result
+}
+
+// 常见用法：把切片分成 chunks 块，每块交给一个 worker，按块的顺序返回结果
+pub fn map_chunks<T, R, F>(data: &[T], chunks: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(usize, &[T]) -> R + Sync,
+{
+    if data.is_empty() {
+        return vec![];
+    }
+    let size = data.len().div_ceil(chunks.max(1));
+    let f = &f;
+    scope(|s| {
+        let handles: Vec<_> = data
+            .chunks(size)
+            .enumerate()
+            .map(|(i, chunk)| s.spawn(move || f(i, chunk)))
+            .collect();
+        let results: Vec<thread::Result<R>> = handles.into_iter().map(|h| h.join()).collect();
+        // 全部 join 之后再重新抛出第一个 panic
+        results
+            .into_iter()
+            .map(|r| r.unwrap_or_else(|payload| panic::resume_unwind(payload)))
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn workers_borrow_runtime_data() {
+        // 运行时构造的 String，不是 &'static str
+        let data: String = (0..8).map(|i| format!("{}\n", i.to_string().repeat(30))).collect();
+        let sums: Vec<u32> = scope(|s| {
+            let handles: Vec<_> = data
+                .split_whitespace()
+                .map(|segment| s.spawn(move || segment.chars().map(|c| c.to_digit(10).unwrap()).sum::<u32>()))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(sums, (0..8).map(|i| i * 30).collect::<Vec<u32>>());
+
+        let numbers: Vec<u64> = (1..=1000).collect();
+        let partial = map_chunks(&numbers, 4, |_, chunk| chunk.iter().sum::<u64>());
+        assert_eq!(partial.len(), 4);
+        assert_eq!(partial.iter().sum::<u64>(), 500_500);
+    }
+
+    #[test]
+    fn panic_is_propagated_after_all_workers_finish() {
+        let finished = AtomicUsize::new(0);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            scope(|s| {
+                s.spawn(|| panic!("should be a digit"));
+                for _ in 0..3 {
+                    s.spawn(|| {
+                        thread::sleep(std::time::Duration::from_millis(50));
+                        finished.fetch_add(1, Ordering::SeqCst);
+                    });
+                }
+            })
+        }));
+        assert_eq!(finished.load(Ordering::SeqCst), 3);
+        let payload = result.unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"should be a digit"));
+    }
+
+    #[test]
+    fn joined_panic_is_not_rethrown() {
+        let err = scope(|s| s.spawn(|| -> u32 { panic!("handled") }).join());
+        assert!(err.is_err());
+    }
+}
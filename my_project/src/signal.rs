@@ -0,0 +1,127 @@
+#![allow(dead_code)]
+
+// 进程级的信号分发
+// 一个信号在整个进程里只能有一个处理函数，后安装的会悄悄替换掉先安装的。supervisor.rs 要在 SIGTERM / SIGINT 时
+// 停止子进程，cancel.rs 要在 Ctrl-C 时取消令牌，如果各自调用 libc::signal，最后只有一方能收到信号。
+// 这里统一安装处理函数，其他模块用 subscribe 订阅：
+// - 信号处理函数里只能做异步信号安全（async-signal-safe）的操作，所以它只把信号编号写进一个管道（self-pipe）
+// - 后台的分发线程从管道读出信号编号，在普通线程里依次调用订阅了该信号的回调，回调里可以加锁、分配内存
+// 订阅在进程的整个生命周期内有效，不能取消。
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+
+type Callback = Arc<dyn Fn(libc::c_int) + Send + Sync>;
+
+struct Registry {
+    installed: Vec<libc::c_int>, // 已经安装了处理函数的信号
+    callbacks: Vec<(libc::c_int, Callback)>,
+}
+
+// 管道的写端，信号处理函数只用到它
+static PIPE_WRITE: AtomicI32 = AtomicI32::new(-1);
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+unsafe fn errno_location() -> *mut libc::c_int {
+    libc::__errno_location()
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+unsafe fn errno_location() -> *mut libc::c_int {
+    libc::__error()
+}
+
+extern "C" fn on_signal(sig: libc::c_int) {
+    let byte = sig as u8;
+    // 写端是非阻塞的：管道满了就丢掉这一次，反正分发线程还没处理完之前的信号。
+    // write 失败时会改 errno，而被打断的代码可能正要读 errno，所以要原样恢复
+    unsafe {
+        let errno = errno_location();
+        let saved = *errno;
+        libc::write(PIPE_WRITE.load(Ordering::SeqCst), &byte as *const u8 as *const libc::c_void, 1);
+        *errno = saved;
+    }
+}
+
+fn registry() -> &'static Mutex<Registry> {
+    static REGISTRY: OnceLock<Mutex<Registry>> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        let mut fds = [0 as libc::c_int; 2];
+        unsafe {
+            assert_eq!(libc::pipe(fds.as_mut_ptr()), 0, "cannot create signal pipe");
+            for fd in fds {
+                // 不让 supervisor.rs 启动的子进程继承这两个文件描述符
+                libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+            }
+            libc::fcntl(fds[1], libc::F_SETFL, libc::fcntl(fds[1], libc::F_GETFL) | libc::O_NONBLOCK);
+        }
+        PIPE_WRITE.store(fds[1], Ordering::SeqCst);
+        thread::Builder::new()
+            .name("signal-dispatch".to_string())
+            .spawn(move || dispatch(fds[0]))
+            .expect("cannot start signal dispatch thread");
+        Mutex::new(Registry { installed: vec![], callbacks: vec![] })
+    })
+}
+
+fn dispatch(read_fd: libc::c_int) {
+    let mut byte = 0u8;
+    loop {
+        let n = unsafe { libc::read(read_fd, &mut byte as *mut u8 as *mut libc::c_void, 1) };
+        if n != 1 {
+            continue; // 被其他信号打断（EINTR）
+        }
+        let sig = libc::c_int::from(byte);
+        // 在锁外调用回调，回调里也可以再 subscribe
+        let callbacks: Vec<Callback> = registry()
+            .lock()
+            .unwrap()
+            .callbacks
+            .iter()
+            .filter(|(s, _)| *s == sig)
+            .map(|(_, f)| Arc::clone(f))
+            .collect();
+        for f in callbacks {
+            f(sig);
+        }
+    }
+}
+
+// 收到 sig 时在分发线程里调用 f。第一次订阅某个信号时安装处理函数，替换掉该信号的默认行为（例如 SIGINT 终止进程）
+pub fn subscribe(sig: libc::c_int, f: impl Fn(libc::c_int) + Send + Sync + 'static) {
+    let mut registry = registry().lock().unwrap();
+    registry.callbacks.push((sig, Arc::new(f)));
+    if registry.installed.contains(&sig) {
+        return;
+    }
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART; // 被打断的系统调用自动重启
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(sig, &action, std::ptr::null_mut());
+    }
+    registry.installed.push(sig);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    #[test]
+    fn every_subscriber_sees_the_signal() {
+        let (tx, rx) = mpsc::channel();
+        for id in 0..2 {
+            let tx = tx.clone();
+            subscribe(libc::SIGUSR2, move |sig| {
+                let _ = tx.send((id, sig));
+            });
+        }
+        unsafe { libc::raise(libc::SIGUSR2) };
+        let mut seen: Vec<_> = (0..2).map(|_| rx.recv_timeout(Duration::from_secs(5)).unwrap()).collect();
+        seen.sort();
+        assert_eq!(seen, vec![(0, libc::SIGUSR2), (1, libc::SIGUSR2)]);
+    }
+}
@@ -0,0 +1,439 @@
+#![allow(dead_code)]
+
+// 进程监督者（supervisor）
+// 基于 std::process 的 Command / Child，让一组长期运行的子进程保持存活：
+// 子进程退出后按照各自的重启策略（always / on-failure / never）重新启动，
+// 重启之间使用指数退避（exponential backoff），并限制一个时间窗口内的重启次数。
+// 收到 SIGTERM / SIGINT 时，把信号转发给所有子进程，等待它们优雅退出。
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::process::{Child, Command, ExitStatus};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{Arc, Mutex, Once, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::signal;
+
+// 调用过 handle_signals 的 Supervisor 的停止标志。只保存 Weak，Supervisor drop 后自然失效
+static SIGNAL_TARGETS: Mutex<Vec<Weak<AtomicI32>>> = Mutex::new(Vec::new());
+
+// 把信号交给每个登记过的 Supervisor，各自在 run 里处理，谁也不会拿走别人的信号
+fn deliver(sig: libc::c_int) {
+    for stop in SIGNAL_TARGETS.lock().unwrap().iter().filter_map(Weak::upgrade) {
+        stop.store(sig, Ordering::SeqCst);
+    }
+}
+
+// 重启策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestartPolicy {
+    Always,    // 无论怎样退出都重启
+    OnFailure, // 只有退出码非 0 或被信号杀死时才重启
+    Never,     // 从不重启
+}
+
+impl RestartPolicy {
+    fn should_restart(&self, status: &ExitStatus) -> bool {
+        match self {
+            RestartPolicy::Always => true,
+            RestartPolicy::OnFailure => !status.success(),
+            RestartPolicy::Never => false,
+        }
+    }
+}
+
+// 指数退避：第 n 次连续重启前等待 initial * multiplier^n，但不超过 max。
+#[derive(Debug, Clone, Copy)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+    pub multiplier: u32,
+}
+
+impl Backoff {
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = self.multiplier.checked_pow(attempt).unwrap_or(u32::MAX);
+        self.initial
+            .checked_mul(factor)
+            .map_or(self.max, |d| d.min(self.max))
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff {
+            initial: Duration::from_millis(100),
+            max: Duration::from_secs(30),
+            multiplier: 2,
+        }
+    }
+}
+
+// 子进程的描述。Command 不能 Clone，所以保存一个每次重启时都会调用的构造闭包。
+pub struct ChildSpec {
+    name: String,
+    make_command: Box<dyn FnMut() -> Command + Send>,
+    policy: RestartPolicy,
+    backoff: Backoff,
+}
+
+impl ChildSpec {
+    pub fn new<F>(name: &str, make_command: F) -> ChildSpec
+    where
+        F: FnMut() -> Command + Send + 'static,
+    {
+        ChildSpec {
+            name: name.to_string(),
+            make_command: Box::new(make_command),
+            policy: RestartPolicy::OnFailure,
+            backoff: Backoff::default(),
+        }
+    }
+
+    pub fn policy(mut self, policy: RestartPolicy) -> ChildSpec {
+        self.policy = policy;
+        self
+    }
+
+    pub fn backoff(mut self, backoff: Backoff) -> ChildSpec {
+        self.backoff = backoff;
+        self
+    }
+}
+
+#[derive(Debug)]
+pub enum SupervisorError {
+    // 启动子进程失败
+    Spawn { name: String, source: io::Error },
+    // 在时间窗口内重启次数超过上限，监督者放弃并关闭所有子进程
+    RestartLimit { name: String, restarts: usize, window: Duration },
+    Io(io::Error),
+}
+
+impl fmt::Display for SupervisorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SupervisorError::Spawn { name, source } => write!(f, "couldn't spawn {}: {}", name, source),
+            SupervisorError::RestartLimit { name, restarts, window } => write!(
+                f,
+                "{} restarted more than {} times within {:?}",
+                name, restarts, window
+            ),
+            SupervisorError::Io(e) => write!(f, "io error: {}", e),
+        }
+    }
+}
+
+impl Error for SupervisorError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SupervisorError::Spawn { source, .. } => Some(source),
+            SupervisorError::Io(e) => Some(e),
+            SupervisorError::RestartLimit { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for SupervisorError {
+    fn from(e: io::Error) -> Self {
+        SupervisorError::Io(e)
+    }
+}
+
+// 可以从其他线程请求监督者停止，效果和收到对应信号一样。
+#[derive(Clone)]
+pub struct StopHandle(Arc<AtomicI32>);
+
+impl StopHandle {
+    pub fn stop(&self) {
+        self.0.store(libc::SIGTERM, Ordering::SeqCst);
+    }
+}
+
+// 每个子进程的运行时状态
+struct Slot {
+    spec: ChildSpec,
+    child: Option<Child>,
+    started_at: Option<Instant>,
+    next_start: Option<Instant>, // 等待退避结束后再启动
+    attempt: u32,                // 连续重启次数，用于计算退避时间
+    restarts: VecDeque<Instant>, // 时间窗口内的重启时刻
+    done: bool,                  // 按策略不再重启
+}
+
+pub struct Supervisor {
+    slots: Vec<Slot>,
+    max_restarts: usize,
+    window: Duration,
+    grace_period: Duration,
+    poll_interval: Duration,
+    stop: Arc<AtomicI32>,
+}
+
+impl Default for Supervisor {
+    fn default() -> Self {
+        Supervisor::new()
+    }
+}
+
+impl Supervisor {
+    pub fn new() -> Supervisor {
+        Supervisor {
+            slots: Vec::new(),
+            max_restarts: 5,
+            window: Duration::from_secs(60),
+            grace_period: Duration::from_secs(5),
+            poll_interval: Duration::from_millis(50),
+            stop: Arc::new(AtomicI32::new(0)),
+        }
+    }
+
+    pub fn child(mut self, spec: ChildSpec) -> Supervisor {
+        self.slots.push(Slot {
+            spec,
+            child: None,
+            started_at: None,
+            next_start: Some(Instant::now()),
+            attempt: 0,
+            restarts: VecDeque::new(),
+            done: false,
+        });
+        self
+    }
+
+    // 每个子进程在 window 时间内最多重启 max_restarts 次
+    pub fn max_restarts(mut self, max_restarts: usize, window: Duration) -> Supervisor {
+        self.max_restarts = max_restarts;
+        self.window = window;
+        self
+    }
+
+    // 转发信号后等待子进程退出的时间，超时后使用 SIGKILL
+    pub fn grace_period(mut self, grace_period: Duration) -> Supervisor {
+        self.grace_period = grace_period;
+        self
+    }
+
+    pub fn poll_interval(mut self, poll_interval: Duration) -> Supervisor {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    // 通过 signal.rs 订阅 SIGTERM 和 SIGINT，之后 run 会把收到的信号转发给子进程。
+    // 和 cancel.rs 的 cancel_on_ctrl_c 可以同时使用；多个 Supervisor 都会收到信号。
+    pub fn handle_signals(self) -> Supervisor {
+        static SUBSCRIBED: Once = Once::new();
+        SUBSCRIBED.call_once(|| {
+            for sig in [libc::SIGTERM, libc::SIGINT] {
+                signal::subscribe(sig, deliver);
+            }
+        });
+        {
+            let mut targets = SIGNAL_TARGETS.lock().unwrap();
+            targets.retain(|t| t.strong_count() > 0);
+            targets.push(Arc::downgrade(&self.stop));
+        }
+        self
+    }
+
+    pub fn stop_handle(&self) -> StopHandle {
+        StopHandle(Arc::clone(&self.stop))
+    }
+
+    // 阻塞运行，直到：
+    // 1. 收到停止请求或 SIGTERM / SIGINT（返回 Ok）
+    // 2. 所有子进程都按策略不再重启（返回 Ok）
+    // 3. 某个子进程超过重启上限（返回 Err）
+    pub fn run(&mut self) -> Result<(), SupervisorError> {
+        loop {
+            if let Some(sig) = self.take_stop_signal() {
+                self.shutdown(sig);
+                return Ok(());
+            }
+            if let Err(e) = self.tick() {
+                self.shutdown(libc::SIGTERM);
+                return Err(e);
+            }
+            if self.slots.iter().all(|slot| slot.done) {
+                return Ok(());
+            }
+            thread::sleep(self.poll_interval);
+        }
+    }
+
+    // 停止请求和信号都记在 stop 里
+    fn take_stop_signal(&self) -> Option<libc::c_int> {
+        match self.stop.swap(0, Ordering::SeqCst) {
+            0 => None,
+            sig => Some(sig),
+        }
+    }
+
+    // 检查一遍所有子进程：回收已退出的，启动退避时间已到的。
+    fn tick(&mut self) -> Result<(), SupervisorError> {
+        let now = Instant::now();
+        let (window, max_restarts) = (self.window, self.max_restarts);
+        for slot in self.slots.iter_mut() {
+            if let Some(child) = slot.child.as_mut() {
+                let status = match child.try_wait()? {
+                    None => continue, // 仍在运行
+                    Some(status) => status,
+                };
+                slot.child = None;
+                if !slot.spec.policy.should_restart(&status) {
+                    slot.done = true;
+                    continue;
+                }
+                // 运行时间足够长就认为它恢复了健康，重置退避
+                let healthy = slot
+                    .started_at
+                    .is_some_and(|t| now.duration_since(t) >= slot.spec.backoff.max);
+                if healthy {
+                    slot.attempt = 0;
+                }
+                while slot.restarts.front().is_some_and(|t| now.duration_since(*t) > window) {
+                    slot.restarts.pop_front();
+                }
+                if slot.restarts.len() >= max_restarts {
+                    return Err(SupervisorError::RestartLimit {
+                        name: slot.spec.name.clone(),
+                        restarts: max_restarts,
+                        window,
+                    });
+                }
+                slot.restarts.push_back(now);
+                slot.next_start = Some(now + slot.spec.backoff.delay(slot.attempt));
+                slot.attempt = slot.attempt.saturating_add(1);
+            }
+            if slot.next_start.is_some_and(|t| t <= now) {
+                let child = (slot.spec.make_command)().spawn().map_err(|source| SupervisorError::Spawn {
+                    name: slot.spec.name.clone(),
+                    source,
+                })?;
+                slot.child = Some(child);
+                slot.started_at = Some(now);
+                slot.next_start = None;
+            }
+        }
+        Ok(())
+    }
+
+    // 把信号转发给所有子进程，等待 grace_period，然后杀掉仍未退出的子进程。
+    fn shutdown(&mut self, sig: libc::c_int) {
+        for child in self.slots.iter_mut().filter_map(|slot| slot.child.as_mut()) {
+            unsafe {
+                libc::kill(child.id() as libc::pid_t, sig);
+            }
+        }
+        let deadline = Instant::now() + self.grace_period;
+        for slot in self.slots.iter_mut() {
+            if let Some(mut child) = slot.child.take() {
+                while Instant::now() < deadline {
+                    match child.try_wait() {
+                        Ok(None) => thread::sleep(Duration::from_millis(10)),
+                        _ => break,
+                    }
+                }
+                if let Ok(None) = child.try_wait() {
+                    let _ = child.kill();
+                }
+                let _ = child.wait();
+            }
+            slot.done = true;
+            slot.next_start = None;
+        }
+    }
+}
+
+impl Drop for Supervisor {
+    // 监督者被 drop 时不留下孤儿进程
+    fn drop(&mut self) {
+        self.shutdown(libc::SIGTERM);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fast_backoff() -> Backoff {
+        Backoff {
+            initial: Duration::from_millis(1),
+            max: Duration::from_millis(10),
+            multiplier: 2,
+        }
+    }
+
+    #[test]
+    fn backoff_grows_and_caps() {
+        let b = Backoff {
+            initial: Duration::from_millis(100),
+            max: Duration::from_secs(1),
+            multiplier: 2,
+        };
+        assert_eq!(b.delay(0), Duration::from_millis(100));
+        assert_eq!(b.delay(2), Duration::from_millis(400));
+        assert_eq!(b.delay(10), Duration::from_secs(1));
+        assert_eq!(b.delay(100), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn never_policy_finishes() {
+        let mut sup = Supervisor::new()
+            .poll_interval(Duration::from_millis(5))
+            .child(ChildSpec::new("true", || Command::new("true")).policy(RestartPolicy::Never));
+        assert!(sup.run().is_ok());
+    }
+
+    #[test]
+    fn restart_limit_is_enforced() {
+        let mut sup = Supervisor::new()
+            .poll_interval(Duration::from_millis(5))
+            .max_restarts(3, Duration::from_secs(60))
+            .child(ChildSpec::new("false", || Command::new("false")).backoff(fast_backoff()));
+        match sup.run() {
+            Err(SupervisorError::RestartLimit { name, restarts, .. }) => {
+                assert_eq!(name, "false");
+                assert_eq!(restarts, 3);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn every_supervisor_sees_the_signal() {
+        let (a, b) = (Supervisor::new().handle_signals(), Supervisor::new().handle_signals());
+        let quiet = Supervisor::new();
+        deliver(libc::SIGTERM);
+        assert_eq!((a.take_stop_signal(), b.take_stop_signal()), (Some(libc::SIGTERM), Some(libc::SIGTERM)));
+        assert_eq!(quiet.take_stop_signal(), None);
+        assert_eq!(a.take_stop_signal(), None);
+    }
+
+    #[test]
+    fn stop_handle_terminates_children() {
+        let mut sup = Supervisor::new()
+            .poll_interval(Duration::from_millis(5))
+            .grace_period(Duration::from_secs(2))
+            .child(
+                ChildSpec::new("sleep", || {
+                    let mut cmd = Command::new("sleep");
+                    cmd.arg("30");
+                    cmd
+                })
+                .policy(RestartPolicy::Always),
+            );
+        let stop = sup.stop_handle();
+        let started = Instant::now();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            stop.stop();
+        });
+        assert!(sup.run().is_ok());
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(sup.slots[0].child.is_none());
+    }
+}
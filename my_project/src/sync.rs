@@ -0,0 +1,397 @@
+#![allow(dead_code)]
+
+// 基于 Mutex + Condvar 的同步原语
+// threads.rs 的注释里只提到“使用 Mutex 或者 Channel”，实际的工作线程代码里却反复手写同样的几种等待逻辑。
+// 这里把它们整理成独立的类型：
+// - Semaphore：计数信号量，acquire 得到一个 Permit，Permit 离开作用域时自动归还（RAII）
+// - CyclicBarrier：可重复使用的屏障，parties 个线程都到达后一起继续，然后进入下一代（generation）
+// - CountDownLatch：倒计数门闩，计数减到 0 之后所有等待者放行，只能用一次
+// - OnceCell：线程安全的延迟初始化，多个线程同时初始化时只有一个真正执行，其余等待结果
+// - WaitGroup：类似 Go 的 sync.WaitGroup，add 登记任务，done 完成任务，wait 等所有任务完成
+// 所有等待都用 while 循环检查条件，防止虚假唤醒（spurious wakeup）。
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+pub struct Semaphore {
+    permits: Mutex<usize>,
+    released: Condvar,
+}
+
+// 持有期间占用一个许可，drop 时归还
+pub struct Permit<'a> {
+    semaphore: &'a Semaphore,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        *self.semaphore.permits.lock().unwrap() += 1;
+        self.semaphore.released.notify_one();
+    }
+}
+
+impl Semaphore {
+    pub fn new(permits: usize) -> Semaphore {
+        Semaphore { permits: Mutex::new(permits), released: Condvar::new() }
+    }
+
+    pub fn acquire(&self) -> Permit<'_> {
+        let mut permits = self.permits.lock().unwrap();
+        while *permits == 0 {
+            permits = self.released.wait(permits).unwrap();
+        }
+        *permits -= 1;
+        Permit { semaphore: self }
+    }
+
+    pub fn try_acquire(&self) -> Option<Permit<'_>> {
+        let mut permits = self.permits.lock().unwrap();
+        if *permits == 0 {
+            return None;
+        }
+        *permits -= 1;
+        Some(Permit { semaphore: self })
+    }
+
+    pub fn acquire_timeout(&self, timeout: Duration) -> Option<Permit<'_>> {
+        let deadline = Instant::now() + timeout;
+        let mut permits = self.permits.lock().unwrap();
+        while *permits == 0 {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return None;
+            }
+            permits = self.released.wait_timeout(permits, remaining).unwrap().0;
+        }
+        *permits -= 1;
+        Some(Permit { semaphore: self })
+    }
+
+    pub fn available(&self) -> usize {
+        *self.permits.lock().unwrap()
+    }
+}
+
+struct BarrierState {
+    arrived: usize,
+    generation: u64,
+}
+
+pub struct CyclicBarrier {
+    parties: usize,
+    state: Mutex<BarrierState>,
+    all_arrived: Condvar,
+}
+
+impl CyclicBarrier {
+    pub fn new(parties: usize) -> CyclicBarrier {
+        assert!(parties > 0, "a barrier needs at least one party");
+        CyclicBarrier {
+            parties,
+            state: Mutex::new(BarrierState { arrived: 0, generation: 0 }),
+            all_arrived: Condvar::new(),
+        }
+    }
+
+    // 最后一个到达的线程返回 true（leader），可以用它做每一轮的汇总工作
+    pub fn wait(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let generation = state.generation;
+        state.arrived += 1;
+        if state.arrived == self.parties {
+            state.arrived = 0;
+            state.generation += 1;
+            self.all_arrived.notify_all();
+            return true;
+        }
+        // 只看代数：被唤醒之前屏障可能已经开始了下一轮，arrived 不再可靠
+        while state.generation == generation {
+            state = self.all_arrived.wait(state).unwrap();
+        }
+        false
+    }
+
+    pub fn parties(&self) -> usize {
+        self.parties
+    }
+}
+
+pub struct CountDownLatch {
+    count: Mutex<usize>,
+    zero: Condvar,
+}
+
+impl CountDownLatch {
+    pub fn new(count: usize) -> CountDownLatch {
+        CountDownLatch { count: Mutex::new(count), zero: Condvar::new() }
+    }
+
+    // 计数已经是 0 时不再减少
+    pub fn count_down(&self) {
+        let mut count = self.count.lock().unwrap();
+        if *count > 0 {
+            *count -= 1;
+            if *count == 0 {
+                self.zero.notify_all();
+            }
+        }
+    }
+
+    pub fn wait(&self) {
+        let mut count = self.count.lock().unwrap();
+        while *count > 0 {
+            count = self.zero.wait(count).unwrap();
+        }
+    }
+
+    // 超时返回 false
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let count = self.count.lock().unwrap();
+        let (count, _) = self.zero.wait_timeout_while(count, timeout, |c| *c > 0).unwrap();
+        *count == 0
+    }
+
+    pub fn count(&self) -> usize {
+        *self.count.lock().unwrap()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum InitState {
+    Empty,
+    Running,
+    Done,
+}
+
+pub struct OnceCell<T> {
+    state: Mutex<InitState>,
+    finished: Condvar,
+    ready: AtomicBool, // 快速路径：初始化完成后 get 不用加锁
+    value: UnsafeCell<Option<T>>,
+}
+
+// value 只在持有 Running 状态的那个线程里写入一次，写入完成（ready 为 true）之后只读
+unsafe impl<T: Send + Sync> Sync for OnceCell<T> {}
+unsafe impl<T: Send> Send for OnceCell<T> {}
+
+impl<T> Default for OnceCell<T> {
+    fn default() -> Self {
+        OnceCell::new()
+    }
+}
+
+impl<T> OnceCell<T> {
+    pub const fn new() -> OnceCell<T> {
+        OnceCell {
+            state: Mutex::new(InitState::Empty),
+            finished: Condvar::new(),
+            ready: AtomicBool::new(false),
+            value: UnsafeCell::new(None),
+        }
+    }
+
+    pub fn get(&self) -> Option<&T> {
+        if self.ready.load(Ordering::Acquire) {
+            // SAFETY: ready 为 true 之后 value 不会再被修改
+            unsafe { (*self.value.get()).as_ref() }
+        } else {
+            None
+        }
+    }
+
+    // 只有一个线程执行 init，其余线程等它完成。init panic 时状态恢复为 Empty，由下一个调用者重试
+    pub fn get_or_init(&self, init: impl FnOnce() -> T) -> &T {
+        if let Some(value) = self.get() {
+            return value;
+        }
+        let mut state = self.state.lock().unwrap();
+        loop {
+            match *state {
+                InitState::Done => return self.get().expect("value is set once done"),
+                InitState::Running => state = self.finished.wait(state).unwrap(),
+                InitState::Empty => break,
+            }
+        }
+        *state = InitState::Running;
+        drop(state);
+
+        struct ResetOnPanic<'a, T>(&'a OnceCell<T>);
+        impl<T> Drop for ResetOnPanic<'_, T> {
+            fn drop(&mut self) {
+                *self.0.state.lock().unwrap() = InitState::Empty;
+                self.0.finished.notify_all();
+            }
+        }
+        let guard = ResetOnPanic(self);
+        let value = init();
+        std::mem::forget(guard);
+
+        // SAFETY: 只有把状态从 Empty 改成 Running 的线程会走到这里，其他线程此时不会读写 value
+        unsafe { *self.value.get() = Some(value) };
+        self.ready.store(true, Ordering::Release);
+        *self.state.lock().unwrap() = InitState::Done;
+        self.finished.notify_all();
+        self.get().expect("value was just set")
+    }
+}
+
+pub struct WaitGroup {
+    pending: Mutex<usize>,
+    done: Condvar,
+}
+
+// WaitGroup::enter 返回的守卫，drop 时调用 done，任务 panic 也不会让 wait 永远等下去
+pub struct WaitGuard<'a> {
+    group: &'a WaitGroup,
+}
+
+impl Drop for WaitGuard<'_> {
+    fn drop(&mut self) {
+        self.group.done();
+    }
+}
+
+impl Default for WaitGroup {
+    fn default() -> Self {
+        WaitGroup::new()
+    }
+}
+
+impl WaitGroup {
+    pub fn new() -> WaitGroup {
+        WaitGroup { pending: Mutex::new(0), done: Condvar::new() }
+    }
+
+    pub fn add(&self, n: usize) {
+        *self.pending.lock().unwrap() += n;
+    }
+
+    pub fn done(&self) {
+        let mut pending = self.pending.lock().unwrap();
+        *pending = pending.checked_sub(1).expect("WaitGroup::done called more times than add");
+        if *pending == 0 {
+            self.done.notify_all();
+        }
+    }
+
+    pub fn enter(&self) -> WaitGuard<'_> {
+        self.add(1);
+        WaitGuard { group: self }
+    }
+
+    pub fn wait(&self) {
+        let mut pending = self.pending.lock().unwrap();
+        while *pending > 0 {
+            pending = self.done.wait(pending).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::thread;
+
+    const THREADS: usize = 16;
+
+    #[test]
+    fn semaphore_never_exceeds_its_permits() {
+        let semaphore = Semaphore::new(3);
+        let (inside, peak) = (AtomicUsize::new(0), AtomicUsize::new(0));
+        thread::scope(|s| {
+            for _ in 0..THREADS {
+                s.spawn(|| {
+                    for _ in 0..200 {
+                        let _permit = semaphore.acquire();
+                        let now = inside.fetch_add(1, Ordering::SeqCst) + 1;
+                        peak.fetch_max(now, Ordering::SeqCst);
+                        thread::yield_now();
+                        inside.fetch_sub(1, Ordering::SeqCst);
+                    }
+                });
+            }
+        });
+        assert!(peak.load(Ordering::SeqCst) <= 3);
+        assert_eq!(semaphore.available(), 3);
+
+        let held: Vec<_> = (0..3).map(|_| semaphore.acquire()).collect();
+        assert!(semaphore.try_acquire().is_none());
+        assert!(semaphore.acquire_timeout(Duration::from_millis(10)).is_none());
+        drop(held);
+        assert!(semaphore.try_acquire().is_some());
+    }
+
+    #[test]
+    fn barrier_keeps_every_round_in_step() {
+        let barrier = CyclicBarrier::new(THREADS);
+        let round = AtomicUsize::new(0);
+        let leaders = AtomicUsize::new(0);
+        thread::scope(|s| {
+            for _ in 0..THREADS {
+                s.spawn(|| {
+                    for r in 0..100 {
+                        // 所有线程都处在同一轮，leader 推进轮数之前没人能进入下一轮
+                        assert_eq!(round.load(Ordering::SeqCst), r);
+                        if barrier.wait() {
+                            leaders.fetch_add(1, Ordering::SeqCst);
+                            round.fetch_add(1, Ordering::SeqCst);
+                        }
+                        barrier.wait();
+                    }
+                });
+            }
+        });
+        assert_eq!(leaders.load(Ordering::SeqCst), 100);
+    }
+
+    #[test]
+    fn latch_and_wait_group_release_waiters() {
+        let latch = CountDownLatch::new(THREADS);
+        let group = WaitGroup::new();
+        let finished = AtomicUsize::new(0);
+        thread::scope(|s| {
+            for _ in 0..THREADS {
+                let guard = group.enter();
+                s.spawn(|| {
+                    let _guard = guard;
+                    latch.count_down();
+                    latch.wait(); // 所有线程都启动后才开始工作
+                    finished.fetch_add(1, Ordering::SeqCst);
+                });
+            }
+            group.wait();
+            assert_eq!(finished.load(Ordering::SeqCst), THREADS);
+        });
+        assert_eq!(latch.count(), 0);
+        assert!(latch.wait_timeout(Duration::ZERO));
+        assert!(!CountDownLatch::new(1).wait_timeout(Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn once_cell_initializes_exactly_once() {
+        for _ in 0..50 {
+            let cell = OnceCell::new();
+            let calls = AtomicUsize::new(0);
+            thread::scope(|s| {
+                for i in 0..THREADS {
+                    let (cell, calls) = (&cell, &calls);
+                    s.spawn(move || {
+                        let value = cell.get_or_init(|| {
+                            calls.fetch_add(1, Ordering::SeqCst);
+                            i
+                        });
+                        assert_eq!(cell.get(), Some(value));
+                    });
+                }
+            });
+            assert_eq!(calls.load(Ordering::SeqCst), 1);
+        }
+
+        let cell = OnceCell::new();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| cell.get_or_init(|| panic!("boom"))));
+        assert!(result.is_err());
+        assert_eq!(*cell.get_or_init(|| 7), 7);
+    }
+}
@@ -0,0 +1,354 @@
+#![allow(dead_code)]
+
+// 收集全部错误的校验
+// exceptions.rs 的 give_commoner / give_princess_2 一次只处理一个 Option，`?` 遇到第一个错误就返回。
+// 校验表单或配置文件时这样很不友好：用户改掉一个错误，再运行又冒出下一个。
+// Validated<T, E> 和 Result 类似，但 Invalid 里是一组错误，并且组合时不会短路：
+// a.zip(b) 在 a、b 都无效时把两边的错误都留下（这就是“应用式（applicative）组合”，区别于 and_then 的“一步接一步”）。
+// Validator 为结构体（例如 helloworld.rs 的 Person）逐个字段添加规则，运行时执行所有规则，
+// 每个错误都带着字段路径，例如 `name`、`address.city`、`emails[1]`。
+// 内置规则的错误信息来自消息目录（i18n.rs）；satisfies 的信息由调用者给出，原样使用。
+use std::fmt;
+use std::iter::FromIterator;
+
+use crate::i18n::{tr, trn};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Validated<T, E> {
+    Valid(T),
+    Invalid(Vec<E>), // 至少有一个错误
+}
+
+impl<T, E> Validated<T, E> {
+    pub fn invalid(error: E) -> Validated<T, E> {
+        Validated::Invalid(vec![error])
+    }
+
+    pub fn is_valid(&self) -> bool {
+        matches!(self, Validated::Valid(_))
+    }
+
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Validated<U, E> {
+        match self {
+            Validated::Valid(t) => Validated::Valid(f(t)),
+            Validated::Invalid(errors) => Validated::Invalid(errors),
+        }
+    }
+
+    pub fn map_err<F>(self, f: impl FnMut(E) -> F) -> Validated<T, F> {
+        match self {
+            Validated::Valid(t) => Validated::Valid(t),
+            Validated::Invalid(errors) => Validated::Invalid(errors.into_iter().map(f).collect()),
+        }
+    }
+
+    // 两边都有效时得到 (T, U)，否则合并两边的错误
+    pub fn zip<U>(self, other: Validated<U, E>) -> Validated<(T, U), E> {
+        match (self, other) {
+            (Validated::Valid(t), Validated::Valid(u)) => Validated::Valid((t, u)),
+            (Validated::Invalid(mut a), Validated::Invalid(b)) => {
+                a.extend(b);
+                Validated::Invalid(a)
+            }
+            (Validated::Invalid(a), _) | (_, Validated::Invalid(a)) => Validated::Invalid(a),
+        }
+    }
+
+    // 依赖前一步结果的校验，只能短路：前一步无效时 f 不会执行
+    pub fn and_then<U>(self, f: impl FnOnce(T) -> Validated<U, E>) -> Validated<U, E> {
+        match self {
+            Validated::Valid(t) => f(t),
+            Validated::Invalid(errors) => Validated::Invalid(errors),
+        }
+    }
+
+    pub fn into_result(self) -> Result<T, Vec<E>> {
+        match self {
+            Validated::Valid(t) => Ok(t),
+            Validated::Invalid(errors) => Err(errors),
+        }
+    }
+}
+
+impl<T, E> From<Result<T, E>> for Validated<T, E> {
+    fn from(result: Result<T, E>) -> Self {
+        match result {
+            Ok(t) => Validated::Valid(t),
+            Err(e) => Validated::invalid(e),
+        }
+    }
+}
+
+// 一组 Validated 合并成一个：全部有效时得到所有值，否则得到所有错误
+impl<T, E> FromIterator<Validated<T, E>> for Validated<Vec<T>, E> {
+    fn from_iter<I: IntoIterator<Item = Validated<T, E>>>(iter: I) -> Self {
+        let mut values = vec![];
+        let mut errors = vec![];
+        for item in iter {
+            match item {
+                Validated::Valid(t) => values.push(t),
+                Validated::Invalid(e) => errors.extend(e),
+            }
+        }
+        if errors.is_empty() {
+            Validated::Valid(values)
+        } else {
+            Validated::Invalid(errors)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    pub path: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(path: &str, message: impl Into<String>) -> FieldError {
+        FieldError { path: path.to_string(), message: message.into() }
+    }
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+impl std::error::Error for FieldError {}
+
+fn join_path(prefix: &str, name: &str) -> String {
+    // 名字为空表示校验值本身，例如列表元素
+    if prefix.is_empty() || name.is_empty() || name.starts_with('[') {
+        format!("{}{}", prefix, name)
+    } else {
+        format!("{}.{}", prefix, name)
+    }
+}
+
+// 一条检查：拿到值和路径前缀，把发现的错误追加到 errors
+type Check<T> = Box<dyn Fn(&T, &str, &mut Vec<FieldError>)>;
+
+pub struct Validator<T> {
+    checks: Vec<Check<T>>,
+}
+
+impl<T: 'static> Default for Validator<T> {
+    fn default() -> Self {
+        Validator::new()
+    }
+}
+
+impl<T: 'static> Validator<T> {
+    pub fn new() -> Validator<T> {
+        Validator { checks: vec![] }
+    }
+
+    // 对 get 取出的字段执行 rule。同一个字段可以多次调用，添加多条规则
+    pub fn field<F: ?Sized + 'static>(
+        mut self,
+        name: &'static str,
+        get: impl Fn(&T) -> &F + 'static,
+        rule: impl Fn(&F) -> Result<(), String> + 'static,
+    ) -> Validator<T> {
+        self.checks.push(Box::new(move |value, prefix, errors| {
+            if let Err(message) = rule(get(value)) {
+                errors.push(FieldError { path: join_path(prefix, name), message });
+            }
+        }));
+        self
+    }
+
+    // 涉及多个字段的规则，例如“结束日期不早于开始日期”，错误记在 name 下
+    pub fn rule(mut self, name: &'static str, rule: impl Fn(&T) -> Result<(), String> + 'static) -> Validator<T> {
+        self.checks.push(Box::new(move |value, prefix, errors| {
+            if let Err(message) = rule(value) {
+                errors.push(FieldError { path: join_path(prefix, name), message });
+            }
+        }));
+        self
+    }
+
+    // 用另一个 Validator 校验嵌套的结构体，路径加上前缀 name.
+    pub fn nested<U: 'static>(
+        mut self,
+        name: &'static str,
+        get: impl Fn(&T) -> &U + 'static,
+        validator: Validator<U>,
+    ) -> Validator<T> {
+        self.checks.push(Box::new(move |value, prefix, errors| {
+            validator.collect(get(value), &join_path(prefix, name), errors);
+        }));
+        self
+    }
+
+    // 校验列表中的每一个元素，路径是 name[i]
+    pub fn each<U: 'static>(
+        mut self,
+        name: &'static str,
+        get: impl Fn(&T) -> &[U] + 'static,
+        validator: Validator<U>,
+    ) -> Validator<T> {
+        self.checks.push(Box::new(move |value, prefix, errors| {
+            let path = join_path(prefix, name);
+            for (i, item) in get(value).iter().enumerate() {
+                validator.collect(item, &format!("{}[{}]", path, i), errors);
+            }
+        }));
+        self
+    }
+
+    fn collect(&self, value: &T, prefix: &str, errors: &mut Vec<FieldError>) {
+        for check in &self.checks {
+            check(value, prefix, errors);
+        }
+    }
+
+    // 执行所有规则，不会在第一个错误处停下
+    pub fn errors(&self, value: &T) -> Vec<FieldError> {
+        let mut errors = vec![];
+        self.collect(value, "", &mut errors);
+        errors
+    }
+
+    pub fn validate(&self, value: T) -> Validated<T, FieldError> {
+        let errors = self.errors(&value);
+        if errors.is_empty() {
+            Validated::Valid(value)
+        } else {
+            Validated::Invalid(errors)
+        }
+    }
+}
+
+// 常用规则
+pub fn non_empty(s: &str) -> Result<(), String> {
+    if s.trim().is_empty() {
+        Err(tr!("validate.non_empty"))
+    } else {
+        Ok(())
+    }
+}
+
+pub fn max_len(max: usize) -> impl Fn(&str) -> Result<(), String> {
+    move |s| {
+        let len = s.chars().count();
+        if len > max {
+            Err(trn!("validate.max_len", max, len = len))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+pub fn in_range<N: PartialOrd + fmt::Display + Copy>(min: N, max: N) -> impl Fn(&N) -> Result<(), String> {
+    move |n| {
+        if *n < min || *n > max {
+            Err(tr!("validate.in_range", min = min, max = max, value = *n))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+pub fn satisfies<F: ?Sized>(check: impl Fn(&F) -> bool, message: &str) -> impl Fn(&F) -> Result<(), String> {
+    let message = message.to_string();
+    move |value| if check(value) { Ok(()) } else { Err(message.clone()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Address {
+        city: String,
+        zip: String,
+    }
+
+    // 和 helloworld.rs 的 Person 一样有 name 和 age，另外加上嵌套的地址和列表
+    #[derive(Debug, Clone, PartialEq)]
+    struct Person {
+        name: String,
+        age: u8,
+        address: Address,
+        emails: Vec<String>,
+    }
+
+    fn person_validator() -> Validator<Person> {
+        let address = Validator::new()
+            .field("city", |a: &Address| a.city.as_str(), non_empty)
+            .field("zip", |a: &Address| a.zip.as_str(), satisfies(|z: &str| z.len() == 6, "must have 6 digits"));
+        let is_email = satisfies(|e: &str| e.contains('@'), "is not an email");
+        let email = Validator::new().field("", |e: &String| e.as_str(), is_email);
+        Validator::new()
+            .field("name", |p: &Person| p.name.as_str(), non_empty)
+            .field("name", |p: &Person| p.name.as_str(), max_len(10))
+            .field("age", |p: &Person| &p.age, in_range(0, 150))
+            .nested("address", |p: &Person| &p.address, address)
+            .each("emails", |p: &Person| p.emails.as_slice(), email)
+            .rule("emails", |p: &Person| match p.age < 13 && !p.emails.is_empty() {
+                true => Err("not allowed under 13".into()),
+                false => Ok(()),
+            })
+    }
+
+    fn potter() -> Person {
+        Person {
+            name: "Potter".to_string(),
+            age: 27,
+            address: Address { city: "London".to_string(), zip: "123456".to_string() },
+            emails: vec!["harry@hogwarts.uk".to_string()],
+        }
+    }
+
+    #[test]
+    fn valid_person_passes() {
+        assert_eq!(person_validator().validate(potter()), Validated::Valid(potter()));
+    }
+
+    #[test]
+    fn every_field_error_is_reported_with_its_path() {
+        let person = Person {
+            name: " ".to_string(),
+            age: 200,
+            address: Address { city: String::new(), zip: "12".to_string() },
+            emails: vec!["ok@example.com".to_string(), "nope".to_string()],
+        };
+        let errors: Vec<String> = person_validator().errors(&person).iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            errors,
+            vec![
+                format!("name: {}", tr!("validate.non_empty")),
+                format!("age: {}", tr!("validate.in_range", min = 0, max = 150, value = 200)),
+                format!("address.city: {}", tr!("validate.non_empty")),
+                "address.zip: must have 6 digits".to_string(),
+                "emails[1]: is not an email".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn applicative_combination_keeps_all_errors() {
+        // 从表单的字符串字段构造 Person：每个字段单独解析，最后用 zip 组合
+        fn from_form(name: &str, age: &str) -> Validated<(String, u8), FieldError> {
+            let name = Validated::from(non_empty(name).map(|_| name.to_string()));
+            let name = name.map_err(|m| FieldError::new("name", m));
+            let age = Validated::from(age.parse::<u8>()).map_err(|e| FieldError::new("age", e.to_string()));
+            name.zip(age)
+        }
+        assert_eq!(from_form("Potter", "27"), Validated::Valid(("Potter".to_string(), 27)));
+        let errors = from_form("", "old").into_result().unwrap_err();
+        assert_eq!(errors.iter().map(|e| e.path.as_str()).collect::<Vec<_>>(), vec!["name", "age"]);
+
+        let parse = |s: &str| Validated::from(s.parse::<u8>().map_err(|_| s.to_string()));
+        let all: Validated<Vec<u8>, String> = vec!["1", "x", "3", "y"].into_iter().map(parse).collect();
+        assert_eq!(all, Validated::Invalid(vec!["x".to_string(), "y".to_string()]));
+
+        // and_then 会短路：第一步失败时第二步不执行
+        let first: Validated<u8, String> = Validated::invalid("first".into());
+        let chained = first.and_then(|_| Validated::<u8, String>::invalid("second".into()));
+        assert_eq!(chained, Validated::Invalid(vec!["first".to_string()]));
+    }
+}
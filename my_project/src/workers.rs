@@ -0,0 +1,235 @@
+#![allow(dead_code)]
+
+// 隔离 panic 的工作组（worker group）
+// threads.rs 用 `let _ = child.join()` 丢掉了 join 的结果，map-reduce 例子则直接 unwrap()：
+// 只要有一段数据里混进了非数字字符，expect("should be a digit") 就会让整个程序退出。
+// WorkerGroup 在每个任务外面套一层 catch_unwind，记录是哪个任务、哪一块输入、什么 panic 信息和调用栈（backtrace），
+// 调用者可以选择遇到第一个失败就停止（fail-fast），或者跑完所有任务后汇总全部错误（collect-all）。
+use std::backtrace::Backtrace;
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::panic;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, Once};
+use std::thread;
+
+use crate::mapreduce::default_workers;
+use crate::pool::{panic_message, run_catching};
+use crate::scope::scope;
+
+thread_local! {
+    // panic 钩子在 panic 发生的位置运行，这时才能拿到有意义的调用栈
+    static LAST_BACKTRACE: RefCell<Option<Backtrace>> = const { RefCell::new(None) };
+    // 当前线程是否正在执行工作组的任务；是的话不打印默认的 panic 信息，由 Report 统一报告。
+    // crash.rs 不看这个标记，而是看 run_catching 的 panic_is_caught()
+    static IN_GROUP_TASK: Cell<bool> = const { Cell::new(false) };
+}
+
+static INSTALL_HOOK: Once = Once::new();
+
+fn in_group_task() -> bool {
+    IN_GROUP_TASK.with(Cell::get)
+}
+
+// 在原有的 panic 钩子之前记录调用栈。只安装一次，并且保留之前的钩子。
+fn install_backtrace_hook() {
+    INSTALL_HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if in_group_task() {
+                LAST_BACKTRACE.with(|bt| *bt.borrow_mut() = Some(Backtrace::force_capture()));
+            } else {
+                previous(info);
+            }
+        }));
+    });
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailurePolicy {
+    FailFast,   // 第一个任务失败后不再启动新任务
+    CollectAll, // 执行所有任务，汇总全部失败
+}
+
+// 一个失败任务的报告
+pub struct TaskFailure<I> {
+    pub task: usize,   // 任务下标，即输入在 inputs 中的位置
+    pub input: I,      // 导致失败的那一块输入
+    pub message: String,
+    pub thread: String,
+    pub backtrace: Backtrace,
+}
+
+impl<I: fmt::Debug> fmt::Debug for TaskFailure<I> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TaskFailure")
+            .field("task", &self.task)
+            .field("input", &self.input)
+            .field("message", &self.message)
+            .field("thread", &self.thread)
+            .finish()
+    }
+}
+
+impl<I> fmt::Display for TaskFailure<I> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "task {} panicked on thread '{}': {}", self.task, self.thread, self.message)
+    }
+}
+
+// 一次运行的结果：results[i] 是第 i 个任务的返回值，失败或被跳过时为 None
+pub struct Report<I, R> {
+    pub results: Vec<Option<R>>,
+    pub failures: Vec<TaskFailure<I>>,
+    pub skipped: Vec<usize>, // fail-fast 模式下没有执行的任务
+}
+
+impl<I, R> Report<I, R> {
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty() && self.skipped.is_empty()
+    }
+
+    // 全部成功时返回所有结果，否则返回失败报告
+    pub fn into_result(self) -> Result<Vec<R>, Vec<TaskFailure<I>>> {
+        if self.failures.is_empty() && self.skipped.is_empty() {
+            Ok(self.results.into_iter().map(|r| r.expect("every task succeeded")).collect())
+        } else {
+            Err(self.failures)
+        }
+    }
+}
+
+enum Outcome<R> {
+    Done(R),
+    Panicked { message: String, thread: String, backtrace: Backtrace },
+}
+
+pub struct WorkerGroup {
+    policy: FailurePolicy,
+    workers: usize,
+}
+
+impl WorkerGroup {
+    pub fn new(policy: FailurePolicy) -> WorkerGroup {
+        WorkerGroup { policy, workers: default_workers() }
+    }
+
+    pub fn workers(mut self, workers: usize) -> WorkerGroup {
+        self.workers = workers.max(1);
+        self
+    }
+
+    // 对每一块输入执行 f，某个任务 panic 不会影响其他任务
+    pub fn run<I, R, F>(&self, inputs: Vec<I>, f: F) -> Report<I, R>
+    where
+        I: Sync,
+        R: Send,
+        F: Fn(&I) -> R + Sync,
+    {
+        install_backtrace_hook();
+        let n = inputs.len();
+        let next = AtomicUsize::new(0);
+        let stop = AtomicBool::new(false);
+        let outcomes: Mutex<Vec<(usize, Outcome<R>)>> = Mutex::new(Vec::with_capacity(n));
+        let (inputs_ref, f) = (&inputs, &f);
+        scope(|s| {
+            for _ in 0..self.workers.min(n) {
+                s.spawn(|| loop {
+                    if stop.load(Ordering::SeqCst) {
+                        return;
+                    }
+                    let i = next.fetch_add(1, Ordering::SeqCst);
+                    if i >= n {
+                        return;
+                    }
+                    let outcome = run_isolated(|| f(&inputs_ref[i]));
+                    if let Outcome::Panicked { .. } = outcome {
+                        if self.policy == FailurePolicy::FailFast {
+                            stop.store(true, Ordering::SeqCst);
+                        }
+                    }
+                    outcomes.lock().unwrap().push((i, outcome));
+                });
+            }
+        });
+
+        let mut outcomes = outcomes.into_inner().unwrap();
+        outcomes.sort_by_key(|(i, _)| *i);
+        let mut outcomes = outcomes.into_iter().peekable();
+        let mut report = Report { results: Vec::with_capacity(n), failures: vec![], skipped: vec![] };
+        for (i, input) in inputs.into_iter().enumerate() {
+            match outcomes.next_if(|(j, _)| *j == i) {
+                Some((_, Outcome::Done(r))) => report.results.push(Some(r)),
+                Some((_, Outcome::Panicked { message, thread, backtrace })) => {
+                    report.results.push(None);
+                    report.failures.push(TaskFailure { task: i, input, message, thread, backtrace });
+                }
+                None => {
+                    report.results.push(None);
+                    report.skipped.push(i);
+                }
+            }
+        }
+        report
+    }
+}
+
+fn run_isolated<R>(task: impl FnOnce() -> R) -> Outcome<R> {
+    IN_GROUP_TASK.with(|flag| flag.set(true));
+    let result = run_catching(task);
+    IN_GROUP_TASK.with(|flag| flag.set(false));
+    match result {
+        Ok(r) => Outcome::Done(r),
+        Err(payload) => Outcome::Panicked {
+            message: panic_message(&*payload),
+            thread: thread::current().name().unwrap_or("<unnamed>").to_string(),
+            backtrace: LAST_BACKTRACE
+                .with(|bt| bt.borrow_mut().take())
+                .unwrap_or_else(Backtrace::disabled),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digit_sum(segment: &&str) -> u32 {
+        segment.chars().map(|c| c.to_digit(10).expect("should be a digit")).sum()
+    }
+
+    #[test]
+    fn collect_all_reports_every_bad_chunk() {
+        let rows = vec!["123", "4x6", "789", "oops", "11"];
+        let report = WorkerGroup::new(FailurePolicy::CollectAll).workers(3).run(rows, digit_sum);
+        assert_eq!(report.results, vec![Some(6), None, Some(24), None, Some(2)]);
+        let bad: Vec<(usize, &str)> = report.failures.iter().map(|f| (f.task, f.input)).collect();
+        assert_eq!(bad, vec![(1, "4x6"), (3, "oops")]);
+        assert!(report.failures[0].message.contains("should be a digit"));
+        assert!(report.skipped.is_empty());
+    }
+
+    #[test]
+    fn string_payloads_and_backtraces_are_kept() {
+        let report = WorkerGroup::new(FailurePolicy::CollectAll).run(vec![7], |n: &i32| -> i32 {
+            panic!("bad row {}", n)
+        });
+        let failure = &report.failures[0];
+        assert_eq!(failure.message, "bad row 7");
+        assert!(failure.to_string().contains("task 0 panicked"));
+        assert!(!failure.backtrace.to_string().is_empty());
+    }
+
+    #[test]
+    fn fail_fast_skips_remaining_tasks() {
+        let inputs: Vec<u32> = (0..100).collect();
+        let report = WorkerGroup::new(FailurePolicy::FailFast).workers(1).run(inputs, |&n| {
+            assert!(n != 3, "three is not allowed");
+            n
+        });
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].input, 3);
+        assert_eq!(report.skipped, (4..100).collect::<Vec<_>>());
+        assert!(report.into_result().is_err());
+    }
+}